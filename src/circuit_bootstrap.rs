@@ -0,0 +1,80 @@
+use concrete_commons::parameters::{LutCountLog, LweSize};
+use concrete_core::backends::core::private as ccore;
+use ccore::math::tensor::AsMutTensor;
+use ccore::math::torus::UnsignedTorus;
+use crate::*;
+use crate::lwe::{constant_sample_extract, conv_lwe_to_rlwe, LWECiphertext, LWEtoRLWEKeyswitchKey};
+use crate::rgsw::RGSWCiphertext;
+use crate::rlwe::RLWECiphertext;
+use crate::server_key::ServerKey;
+use crate::error::MfheError;
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+/// The key material needed on top of the `ServerKey` to turn LWE ciphertexts back into RGSW
+/// ciphertexts under the joint key, see `Coordinator::aggregate_circuit_bootstrapping_key`.
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+pub struct CircuitBootstrappingKey<T = Scalar> {
+    /// RGSW encryptions of the coefficients of the joint LWE key, with the keyswitching
+    /// decomposition, used to convert the bootstrapped LWE ciphertexts to RLWE ones.
+    pub lwe_to_rlwe_ksk: LWEtoRLWEKeyswitchKey<T>,
+    /// RGSW(-s) for the joint RLWE key s, with the decomposition `Context::negs_base_log`.
+    pub neg_s: RGSWCiphertext<T>,
+}
+
+/// Circuit bootstrapping: compute RGSW(m) under the joint RLWE key from an LWE encryption of the
+/// bit m with the gate encoding (+-1/8, see `encode_gate`). The output has the RGSW decomposition
+/// of ctx, so it can be used as the selector of `RGSWCiphertext::cmux`.
+///
+/// For every level l, lwe_in is bootstrapped with the sign test polynomial q/(2 B^l), keyswitched
+/// and shifted by q/(2 B^l), which gives LWE(m q/B^l), then converted to RLWE(m q/B^l). These
+/// RLWE ciphertexts are the odd rows of the output, the even ones are their products with RGSW(-s)
+/// (see `decomposed_rlwe_to_rgsw`). The noise of the last level must stay below q/(2 B^l), so the
+/// RGSW decomposition of ctx bounds the noise the bootstrapping may add.
+pub fn circuit_bootstrap<T: UnsignedTorus>(
+    lwe_in: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+    key: &CircuitBootstrappingKey<T>,
+    ctx: &Context,
+) -> Result<RGSWCiphertext<T>, MfheError> {
+    let lwe_dimension = server_key.lwe_size().0 - 1;
+    if key.lwe_to_rlwe_ksk.lwe_dimension() != lwe_dimension {
+        return Err(MfheError::DimensionMismatch {
+            what: "LWE to RLWE keyswitching key",
+            expected: lwe_dimension,
+            found: key.lwe_to_rlwe_ksk.lwe_dimension(),
+        });
+    }
+    let base_log = ctx.rgsw_base_log.0;
+    let level_count = ctx.rgsw_level_count.0;
+    // the half step of the last level is q/(2 B^l)
+    if base_log * level_count >= T::BITS {
+        return Err(MfheError::ParameterMismatch("RGSW decomposition"));
+    }
+
+    let poly_size = server_key.polynomial_size();
+    let mut buffers = server_key.gen_fourier_buffers();
+    let mut levels = Vec::with_capacity(level_count);
+
+    for level in 1..=level_count {
+        let half_step = T::ONE << (T::BITS - base_log * level - 1);
+
+        let mut accumulator = RLWECiphertext::allocate(poly_size);
+        accumulator.get_mut_body().as_mut_tensor().fill_with_element(half_step);
+        let output_bootstrap = server_key.multi_value_bootstrap_with_buf(lwe_in, &mut accumulator, LutCountLog(0), &mut buffers)?;
+
+        let mut extracted = LWECiphertext::allocate(LweSize(poly_size.0 + 1));
+        constant_sample_extract(&mut extracted, &output_bootstrap);
+        let mut switched = LWECiphertext::allocate(server_key.lwe_size());
+        server_key.keyswitch(&mut switched, &extracted);
+
+        // +-q/(2 B^l) + q/(2 B^l) is 0 or q/B^l
+        let body = switched.get_body().0.wrapping_add(half_step);
+        switched.get_mut_body().0 = body;
+
+        levels.push(conv_lwe_to_rlwe(&key.lwe_to_rlwe_ksk, &switched, ctx));
+    }
+
+    Ok(decomposed_rlwe_to_rgsw(&levels, &key.neg_s, ctx))
+}
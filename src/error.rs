@@ -0,0 +1,74 @@
+use std::fmt;
+
+use crate::*;
+use crate::params::ContextError;
+#[cfg(feature = "serde_serialize")]
+use crate::serialization::SerializationError;
+
+/// The errors returned by the public API. Misuse (keys or ciphertexts of the wrong size, values
+/// that can not be encoded, messages of a party that do not follow the protocol) is reported
+/// instead of panicking, so that one bad message does not crash the whole service.
+#[derive(Debug)]
+pub enum MfheError {
+    /// The parameters can not be used to build a context.
+    InvalidParameters(ContextError),
+    /// An object (named by the str) was built with other parameters than the ones it is used with.
+    ParameterMismatch(&'static str),
+    /// A key or ciphertext (named by what) does not have the expected size.
+    DimensionMismatch { what: &'static str, expected: usize, found: usize },
+    /// The scalar can not be encoded with the encoding named by the str.
+    InvalidEncoding(&'static str, Scalar),
+    /// The masks of the public key share of a party (given by its id) are not the common ones.
+    InconsistentPublicKeyShare(usize),
+    /// A share carries a party id that is not in [0, k) or that was already used by another share.
+    InvalidPartyId(usize),
+    /// The masks of the joint public key received by a party are not the common ones.
+    InconsistentJointPublicKey,
+    /// The key material (named by the str) needed by the operation is missing, e.g. because a
+    /// round of the key generation was not run.
+    MissingKeyMaterial(&'static str),
+    /// An encoded object can not be loaded.
+    #[cfg(feature = "serde_serialize")]
+    Serialization(SerializationError),
+}
+
+impl fmt::Display for MfheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MfheError::InvalidParameters(err) =>
+                write!(f, "invalid parameters: {}", err),
+            MfheError::ParameterMismatch(what) =>
+                write!(f, "the {} was generated with other parameters", what),
+            MfheError::DimensionMismatch { what, expected, found } =>
+                write!(f, "the {} has size {}, expected {}", what, found, expected),
+            MfheError::InvalidEncoding(encoding, x) =>
+                write!(f, "{} is not a valid input of the {} encoding", x, encoding),
+            MfheError::InconsistentPublicKeyShare(id) =>
+                write!(f, "the public key share of party {} does not use the common masks", id),
+            MfheError::InvalidPartyId(id) =>
+                write!(f, "the party id {} is out of range or used by several shares", id),
+            MfheError::InconsistentJointPublicKey =>
+                write!(f, "the joint public key does not use the common masks"),
+            MfheError::MissingKeyMaterial(what) =>
+                write!(f, "the {} is missing", what),
+            #[cfg(feature = "serde_serialize")]
+            MfheError::Serialization(err) =>
+                write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MfheError {}
+
+impl From<ContextError> for MfheError {
+    fn from(err: ContextError) -> Self {
+        MfheError::InvalidParameters(err)
+    }
+}
+
+#[cfg(feature = "serde_serialize")]
+impl From<SerializationError> for MfheError {
+    fn from(err: SerializationError) -> Self {
+        MfheError::Serialization(err)
+    }
+}
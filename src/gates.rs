@@ -0,0 +1,163 @@
+use concrete_commons::parameters::LweSize;
+use concrete_core::backends::core::private as ccore;
+use ccore::crypto::lwe::LweBody;
+use ccore::math::tensor::AsMutTensor;
+use ccore::math::torus::UnsignedTorus;
+use crate::*;
+use crate::lwe::{constant_sample_extract, LWECiphertext};
+use crate::rlwe::RLWECiphertext;
+use crate::server_key::ServerKey;
+use crate::error::MfheError;
+
+/// Encoding of 1/8 on the torus, i.e. the encoding of a true bit (see `encode_gate`). The gate
+/// encodings are +-1/8, so it is also the decoding margin.
+pub(crate) const EIGHTH: Scalar = 1 << (Scalar::BITS - 3);
+
+/// `EIGHTH` on the torus of T.
+fn eighth<T: UnsignedTorus>() -> T {
+    T::ONE << (T::BITS - 3)
+}
+
+/// Encoding of 1/4 on the torus of T.
+fn quarter<T: UnsignedTorus>() -> T {
+    T::ONE << (T::BITS - 2)
+}
+
+/// Add a constant to the body of ct.
+fn add_constant<T: UnsignedTorus>(ct: &mut LWECiphertext<T>, constant: T) {
+    let updated_body = LweBody(ct.get_body().0.wrapping_add(constant));
+    ct.get_mut_body().clone_from(&updated_body);
+}
+
+/// Bootstrap lwe_in with the sign test polynomial (every coefficient is 1/8) and sample extract
+/// the constant coefficient. The output is encrypted under the RLWE key seen as an LWE key.
+fn bootstrap_without_keyswitch<T: UnsignedTorus>(lwe_in: &LWECiphertext<T>, server_key: &ServerKey<T>) -> Result<LWECiphertext<T>, MfheError> {
+    let poly_size = server_key.polynomial_size();
+    let mut accumulator = RLWECiphertext::allocate(poly_size);
+    accumulator.get_mut_body().as_mut_tensor().fill_with_element(eighth::<T>());
+
+    let output_bootstrap = server_key.bootstrap(lwe_in, &mut accumulator)?;
+
+    let mut lwe_out = LWECiphertext::allocate(LweSize(poly_size.0 + 1));
+    constant_sample_extract(&mut lwe_out, &output_bootstrap);
+    Ok(lwe_out)
+}
+
+/// Bootstrap lwe_in and keyswitch the result back to the LWE key.
+fn bootstrap_gate<T: UnsignedTorus>(lwe_in: &LWECiphertext<T>, server_key: &ServerKey<T>) -> Result<LWECiphertext<T>, MfheError> {
+    let lwe_out = bootstrap_without_keyswitch(lwe_in, server_key)?;
+    let mut switched_ciphertext = LWECiphertext::allocate(server_key.lwe_size());
+    server_key.keyswitch(&mut switched_ciphertext, &lwe_out);
+    Ok(switched_ciphertext)
+}
+
+/// Homomorphic NAND gate: bootstraps (0, 1/8) - ct1 - ct2.
+pub fn nand<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = LWECiphertext::allocate(ct1.lwe_size());
+    lwe_ct.update_with_sub(ct1.clone());
+    lwe_ct.update_with_sub(ct2.clone());
+    add_constant(&mut lwe_ct, eighth());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic AND gate: bootstraps (0, -1/8) + ct1 + ct2.
+pub fn and<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = ct1.clone();
+    lwe_ct.update_with_add(ct2.clone());
+    add_constant(&mut lwe_ct, eighth::<T>().wrapping_neg());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic OR gate: bootstraps (0, 1/8) + ct1 + ct2.
+pub fn or<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = ct1.clone();
+    lwe_ct.update_with_add(ct2.clone());
+    add_constant(&mut lwe_ct, eighth());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic NOR gate: bootstraps (0, -1/8) - ct1 - ct2.
+pub fn nor<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = LWECiphertext::allocate(ct1.lwe_size());
+    lwe_ct.update_with_sub(ct1.clone());
+    lwe_ct.update_with_sub(ct2.clone());
+    add_constant(&mut lwe_ct, eighth::<T>().wrapping_neg());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic XOR gate: bootstraps (0, 1/4) + 2*(ct1 + ct2).
+pub fn xor<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = ct1.clone();
+    lwe_ct.update_with_add(ct2.clone());
+    lwe_ct.update_with_scalar_mul(T::TWO);
+    add_constant(&mut lwe_ct, quarter());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic XNOR gate: bootstraps (0, -1/4) - 2*(ct1 + ct2).
+pub fn xnor<T: UnsignedTorus>(
+    ct1: &LWECiphertext<T>,
+    ct2: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut lwe_ct = ct1.clone();
+    lwe_ct.update_with_add(ct2.clone());
+    lwe_ct.update_with_scalar_mul(T::TWO);
+    lwe_ct.update_with_neg();
+    add_constant(&mut lwe_ct, quarter::<T>().wrapping_neg());
+    bootstrap_gate(&lwe_ct, server_key)
+}
+
+/// Homomorphic NOT gate. Since true and false are encoded as 1/8 and -1/8, negating the
+/// ciphertext is enough and no bootstrapping is needed.
+pub fn not<T: UnsignedTorus>(ct: &LWECiphertext<T>) -> LWECiphertext<T> {
+    let mut lwe_ct = ct.clone();
+    lwe_ct.update_with_neg();
+    lwe_ct
+}
+
+/// Homomorphic MUX gate: outputs ct1 if sel is true and ct0 otherwise.
+/// We bootstrap sel AND ct1 and (NOT sel) AND ct0 without keyswitching, add (0, 1/8) to their sum
+/// and keyswitch the result.
+pub fn mux<T: UnsignedTorus>(
+    sel: &LWECiphertext<T>,
+    ct1: &LWECiphertext<T>,
+    ct0: &LWECiphertext<T>,
+    server_key: &ServerKey<T>,
+) -> Result<LWECiphertext<T>, MfheError> {
+    let mut and1 = sel.clone();
+    and1.update_with_add(ct1.clone());
+    add_constant(&mut and1, eighth::<T>().wrapping_neg());
+
+    let mut and0 = ct0.clone();
+    and0.update_with_sub(sel.clone());
+    add_constant(&mut and0, eighth::<T>().wrapping_neg());
+
+    let mut lwe_out = bootstrap_without_keyswitch(&and1, server_key)?;
+    lwe_out.update_with_add(bootstrap_without_keyswitch(&and0, server_key)?);
+    add_constant(&mut lwe_out, eighth());
+
+    let mut switched_ciphertext = LWECiphertext::allocate(server_key.lwe_size());
+    server_key.keyswitch(&mut switched_ciphertext, &lwe_out);
+    Ok(switched_ciphertext)
+}
@@ -3,6 +3,17 @@
 pub mod rlwe;
 pub mod rgsw;
 pub mod lwe;
+pub mod gates;
+pub mod server_key;
+pub mod lut;
+pub mod params;
+pub mod party;
+pub mod error;
+pub mod noise;
+pub mod profiling;
+pub mod circuit_bootstrap;
+#[cfg(feature = "serde_serialize")]
+pub mod serialization;
 
 use std::ops::Neg;
 
@@ -19,8 +30,12 @@ use concrete_core::backends::core::private::crypto::bootstrap::FourierBuffers;
 use concrete_core::backends::core::private::math::fft::{Complex64, FourierPolynomial, AlignedVec};
 use concrete_core::backends::core::private::math::polynomial::PolynomialList;
 use num_traits::{One, Zero};
+use concrete_core::backends::core::private::math::torus::UnsignedTorus;
 use crate::rgsw::RGSWCiphertext;
 use crate::rlwe::*;
+use crate::params::{ContextBuilder, ContextError, KeySizeReport, Seeds, SecurityLevel};
+use crate::error::MfheError;
+use crate::noise::NoiseReport;
 
 pub type Scalar = u64;
 pub type SignedScalar = i64;
@@ -31,6 +46,10 @@ pub struct Context {
     pub random_generator: RandomGenerator,
     pub secret_generator: SecretRandomGenerator,
     pub encryption_generator: EncryptionRandomGenerator,
+    /// The seeds the generators were created with, see `Seeds`.
+    pub seeds: Seeds,
+    /// Whether the noise stream is seeded too, see `ContextBuilder::replay`.
+    pub replay: bool,
     pub std: LogStandardDev,
     //pub std_lwe: StandardDev,
     pub std_lwe: LogStandardDev,
@@ -47,48 +66,58 @@ pub struct Context {
     pub m: usize,
     pub glwe_size: GlweSize,
     pub k: usize,
+    /// The number of bits of the torus of the keys and ciphertexts, see `ContextBuilder::torus_bits`.
+    pub torus_bits: usize,
+    /// The security level m was checked against, see `SecurityLevel::public_key_bits`.
+    pub security: SecurityLevel,
 }
 
 impl Context {
+    /// Return a context with the default parameters (n = 494, N = 2048, k = 4).
+    /// Use `ContextBuilder` to choose other parameters.
     pub fn default() -> Context {
-        let random_generator = RandomGenerator::new(None);
-        let secret_generator = SecretRandomGenerator::new(None);
-        let encryption_generator = EncryptionRandomGenerator::new(None);
-        let std = LogStandardDev::from_log_standard_dev(-55.);
-        let std_lwe = LogStandardDev::from_log_standard_dev(-47.); //std = 2^17 / 2^64 
-        let std_rlwe = StandardDev::from_standard_dev(9.76908e-16); //std = 1.85*2^(4.2) / 2^64
-        let poly_size = PolynomialSize(2048);
-        let base_log = DecompositionBaseLog(4);
-        let level_count = DecompositionLevelCount(15);
-        let ks_base_log = DecompositionBaseLog(13);
-        let ks_level_count = DecompositionLevelCount(3);
-        let rgsw_base_log = DecompositionBaseLog(10);
-        let rgsw_level_count = DecompositionLevelCount(5);
-        let negs_base_log = DecompositionBaseLog(30);
-        let negs_level_count = DecompositionLevelCount(2);
-        let m = 3*64;
-        let glwe_size = GlweSize(495);
-        let k = 4;
-        Context {
-            random_generator,
-            secret_generator,
-            encryption_generator,
-            std,
-            std_lwe,
-            std_rlwe,
-            poly_size,
-            base_log,
-            level_count,
-            ks_base_log,
-            ks_level_count,
-            rgsw_base_log,
-            rgsw_level_count,
-            negs_base_log,
-            negs_level_count,
-            m,
-            glwe_size,
-            k
+        ContextBuilder::new().build().expect("the default parameters are consistent")
+    }
+
+    /// Return a context with the default parameters whose generators are seeded from master_seed.
+    /// The noise stream is drawn from the system entropy, see `ContextBuilder::replay`.
+    pub fn with_seed(master_seed: u128) -> Context {
+        ContextBuilder::new().seed(master_seed).build().expect("the default parameters are consistent")
+    }
+
+    /// Return a context with the same parameters whose generators are seeded from the random
+    /// generator of self, e.g. to encrypt in parallel within one party (see
+    /// `LWESecretKey::par_encrypt_rgsw_bits`). A forked context is known to the owner of self, so
+    /// every party uses a `fresh` one instead.
+    pub fn fork(&mut self) -> Context {
+        let seeds = Seeds::from_generator(&mut self.random_generator);
+        ContextBuilder::from_context(self).seeds(seeds).build().expect("the parameters of self are consistent")
+    }
+
+    /// Return a context with the same parameters whose generators are seeded from the system
+    /// entropy, e.g. the context of a party, which must not be derivable from the one of anybody
+    /// else.
+    pub fn fresh(&self) -> Context {
+        ContextBuilder::from_context(self).seeds(Seeds::fresh()).build().expect("the parameters of self are consistent")
+    }
+
+    /// Check that T is the torus of self, i.e. that the decompositions of self fit in T.
+    pub fn check_torus<T: UnsignedTorus>(&self) -> Result<(), ContextError> {
+        if T::BITS != self.torus_bits {
+            return Err(ContextError::TorusMismatch(self.torus_bits, T::BITS));
         }
+        Ok(())
+    }
+
+    /// Predict the size of the keys generated with the parameters of self, see `KeySizeReport`.
+    pub fn key_size_report(&self) -> KeySizeReport {
+        KeySizeReport::from_context(self)
+    }
+
+    /// Predict the noise of the bootstrapping and the failure probability of a gate with the
+    /// parameters of self, see `NoiseReport`.
+    pub fn noise_report(&self) -> NoiseReport {
+        NoiseReport::from_context(self)
     }
 
     /// Output the plaintext count.
@@ -130,14 +159,30 @@ impl Context {
     }
 
     /// Allocate and return buffers that are used for FFT.
-    pub fn gen_fourier_buffers(&self) -> FourierBuffers<Scalar> {
+    pub fn gen_fourier_buffers<T: UnsignedTorus>(&self) -> FourierBuffers<T> {
         FourierBuffers::new(self.poly_size, GlweSize(2))
     }
 }
 
+/// Check that the party ids of a list of shares (named by what) are exactly the ids of the k
+/// parties, i.e. that there is one share per party in [0, k).
+pub(crate) fn check_party_ids(what: &'static str, ids: impl ExactSizeIterator<Item=usize>, k: usize) -> Result<(), MfheError> {
+    if ids.len() != k {
+        return Err(MfheError::DimensionMismatch { what, expected: k, found: ids.len() });
+    }
+    let mut seen = vec![false; k];
+    for id in ids {
+        match seen.get_mut(id) {
+            Some(used) if !*used => *used = true,
+            _ => return Err(MfheError::InvalidPartyId(id)),
+        }
+    }
+    Ok(())
+}
+
 /// Multiply a polynomial as tensor by a scalar
-pub(crate) fn mul_const<C>(poly: &mut Tensor<C>, c: Scalar)
-    where C: AsMutSlice<Element=Scalar>
+pub(crate) fn mul_const<C, T: UnsignedTorus>(poly: &mut Tensor<C>, c: T)
+    where C: AsMutSlice<Element=T>
 {
     for coeff in poly.iter_mut() {
         *coeff = coeff.wrapping_mul(c);
@@ -157,9 +202,12 @@ pub fn plaintext_index(index: usize, ctnt: Scalar, ctx: &mut Context) -> Plainte
 }
 
 /// Encode binary x as x*(q/2)
-pub fn binary_encode(x: &mut Scalar) {
-    assert!(*x == 0 || *x == 1);
-    *x = *x << (Scalar::BITS - 1)
+pub fn binary_encode(x: &mut Scalar) -> Result<(), MfheError> {
+    if *x > 1 {
+        return Err(MfheError::InvalidEncoding("binary", *x));
+    }
+    *x = *x << (Scalar::BITS - 1);
+    Ok(())
 }
 
 pub fn binary_decode(x: &mut Scalar) {
@@ -173,27 +221,14 @@ pub fn binary_decode(x: &mut Scalar) {
     }
 }
 
-/// encode x as 2^64 / 8 or - 2^64 / 8 depending on sign
-pub fn encode_accumulator(x: &mut Scalar) {
-    let shift = (Scalar::BITS as usize) - 3;
-    let y = *x as SignedScalar;
-
-    if y < 0 {
-        *x = 16140901064495857664;
-        //*x = 3758096384;
-    } else {
-        *x = 1 << shift; 
-    }
-}
-
-/// encode x as 2^32 / 8 or - 2^32 / 8 depending on sign
-pub fn encode_accumulator32(x: &mut u32) {
-    let shift = 32 - 3;
-    let y = *x as i32;
-    if y < 0 {
-        *x = 3758096384;
+/// encode x as q / 8 or - q / 8 depending on sign, where q = 2^T::BITS
+pub fn encode_accumulator<T: UnsignedTorus>(x: &mut T) {
+    let shift = T::BITS - 3;
+    // the most significant bit is the sign of x
+    if (*x >> (T::BITS - 1)) == T::ONE {
+        *x = T::ZERO.wrapping_sub(T::ONE << shift);
     } else {
-        *x = 1 << shift; 
+        *x = T::ONE << shift;
     }
 }
 
@@ -202,47 +237,26 @@ pub fn encode(x: &mut Scalar, ctx: &Context){
     *x = *x*(1 << shift);
 }
 
-/// encode x as x * 2^64 / 8 or - x * 2^64 / 8 depending on sign 
-pub fn encode_gate(x: &mut Scalar){
-    let shift = (Scalar::BITS as usize) - 3;
-    if *x != Scalar::zero() {
-        *x = x.wrapping_mul(1 << shift);
+/// encode x as x * q / 8 or - q / 8 depending on x, where q = 2^T::BITS
+pub fn encode_gate<T: UnsignedTorus>(x: &mut T){
+    let shift = T::BITS - 3;
+    if *x != T::ZERO {
+        *x = x.wrapping_mul(T::ONE << shift);
     } else {
-        *x = 16140901064495857664;
-    }    
-}
-
-/// encode x as x * 2^32 / 8 or - x * 2^32 / 8 depending on sign
-pub fn encode_gate32(x: &mut u32){
-    let shift = 32 - 3;
-    if *x != 0u32 {
-        *x = x.wrapping_mul(1 << shift);
-    } else {
-        *x = 3758096384;
-    }    
+        *x = T::ZERO.wrapping_sub(T::ONE << shift);
+    }
 }
 
 
-pub fn decode_gate(x: &mut Scalar){
-    let shift = ((Scalar::BITS as usize) - 3) as Scalar;
+pub fn decode_gate<T: UnsignedTorus>(x: &mut T){
+    let shift = T::BITS - 3;
 
-    if *x == (1 << shift) {
-        *x = Scalar::one();
+    if *x == (T::ONE << shift) {
+        *x = T::ONE;
     } else {
-        *x = Scalar::zero();
+        *x = T::ZERO;
     }
-    
-}
-
-pub fn decode_gate32(x: &mut u32){
-    let shift = 32 - 3 as u32;
 
-    if *x == (1 << shift) {
-        *x = 1u32;
-    } else {
-        *x = 0u32;
-    }
-    
 }
 
 pub fn decode(x: &mut Scalar, ctx: &Context){
@@ -256,7 +270,7 @@ pub fn decode_acc(x: &mut Scalar, ctx: &Context){
 
 
 /// Encode ternary x as x*(q/3)
-pub fn ternary_encode(x: &mut Scalar) {
+pub fn ternary_encode(x: &mut Scalar) -> Result<(), MfheError> {
     const THIRD: Scalar = (Scalar::MAX as f64 / 3.0) as Scalar;
     if *x == 0 {
         *x = 0;
@@ -265,8 +279,9 @@ pub fn ternary_encode(x: &mut Scalar) {
     } else if *x == Scalar::MAX {
         *x = 2*THIRD;
     } else {
-        panic!("not a ternary scalar")
+        return Err(MfheError::InvalidEncoding("ternary", *x));
     }
+    Ok(())
 }
 
 pub fn ternary_decode(x: &mut Scalar) {
@@ -283,12 +298,13 @@ pub fn ternary_decode(x: &mut Scalar) {
 }
 
 /// Encode a binary polynomial.
-pub fn poly_binary_encode<C>(xs: &mut Polynomial<C>)
+pub fn poly_binary_encode<C>(xs: &mut Polynomial<C>) -> Result<(), MfheError>
     where C: AsMutSlice<Element=Scalar>
 {
     for coeff in xs.coefficient_iter_mut() {
-        binary_encode(coeff);
+        binary_encode(coeff)?;
     }
+    Ok(())
 }
 
 pub fn poly_encode<C>(xs : &mut Polynomial<C>, ctx: &Context)
@@ -341,12 +357,13 @@ pub fn poly_binary_decode<C>(xs: &mut Polynomial<C>)
 }
 
 /// Encode a ternary polynomial.
-pub fn poly_ternary_encode<C>(xs: &mut Polynomial<C>)
+pub fn poly_ternary_encode<C>(xs: &mut Polynomial<C>) -> Result<(), MfheError>
     where C: AsMutSlice<Element=Scalar>
 {
     for coeff in xs.coefficient_iter_mut() {
-        ternary_encode(coeff);
+        ternary_encode(coeff)?;
     }
+    Ok(())
 }
 
 pub fn poly_ternary_decode<C>(xs: &mut Polynomial<C>)
@@ -357,7 +374,7 @@ pub fn poly_ternary_decode<C>(xs: &mut Polynomial<C>)
     }
 }
 
-pub fn decomposed_rlwe_to_rgsw(cs: &Vec<RLWECiphertext>, neg_s: &RGSWCiphertext, ctx: &Context) -> RGSWCiphertext {
+pub fn decomposed_rlwe_to_rgsw<T: UnsignedTorus>(cs: &Vec<RLWECiphertext<T>>, neg_s: &RGSWCiphertext<T>, ctx: &Context) -> RGSWCiphertext<T> {
     let mut out = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log,ctx.rgsw_level_count);
     let mut buffers = FourierBuffers::new(ctx.poly_size, GlweSize(2));
     for (i, mut c) in out.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
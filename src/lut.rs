@@ -0,0 +1,215 @@
+use concrete_commons::parameters::{LutCountLog, LweSize, PolynomialSize};
+use concrete_core::backends::core::private as ccore;
+use ccore::math::polynomial::MonomialDegree;
+use ccore::math::tensor::AsMutTensor;
+use crate::*;
+use crate::lwe::{constant_sample_extract, LWECiphertext};
+use crate::rlwe::RLWECiphertext;
+use crate::server_key::ServerKey;
+use crate::error::MfheError;
+use crate::params::ContextError;
+use rayon::prelude::*;
+
+/// Return the scaling factor of messages modulo message_modulus.
+/// One bit of padding is kept on top of the message, so Delta = 2^64 / (2 * message_modulus).
+pub fn message_delta(message_modulus: usize) -> Scalar {
+    1 << (Scalar::BITS as usize - 1 - message_modulus.trailing_zeros() as usize)
+}
+
+/// Encode the message x in [0, message_modulus) as x * Delta.
+pub fn encode_message(x: &mut Scalar, message_modulus: usize) -> Result<(), MfheError> {
+    if *x >= message_modulus as Scalar {
+        return Err(MfheError::InvalidEncoding("message", *x));
+    }
+    *x = x.wrapping_mul(message_delta(message_modulus));
+    Ok(())
+}
+
+/// Decode x by rounding to the closest multiple of Delta.
+pub fn decode_message(x: &mut Scalar, message_modulus: usize) {
+    let delta = message_delta(message_modulus);
+    let rounded = x.wrapping_add(delta >> 1) / delta;
+    *x = rounded % (message_modulus as Scalar);
+}
+
+/// The message modulus must be a power of two so that the boxes split the N coefficients evenly.
+fn check_message_modulus(message_modulus: usize, poly_size: PolynomialSize) -> Result<(), MfheError> {
+    if !message_modulus.is_power_of_two() {
+        return Err(ContextError::MessageModulusNotPowerOfTwo(message_modulus).into());
+    }
+    if message_modulus > poly_size.0 {
+        return Err(ContextError::MessageModulusTooLarge(message_modulus, poly_size.0).into());
+    }
+    Ok(())
+}
+
+/// A lookup table for the programmable bootstrapping.
+/// It holds the test polynomial (as a trivial RLWE encryption) of a function f over the messages
+/// modulo message_modulus: the N coefficients are split in message_modulus boxes of size
+/// N / message_modulus, the m-th box holds f(m) * Delta and the polynomial is rotated by half a
+/// box so that the noise of the input can be either positive or negative.
+#[derive(Debug, Clone)]
+pub struct LookupTable {
+    pub(crate) accumulator: RLWECiphertext,
+    pub(crate) message_modulus: usize,
+}
+
+impl LookupTable {
+    pub fn new<F>(f: F, message_modulus: usize, poly_size: PolynomialSize) -> Result<LookupTable, MfheError>
+    where
+        F: Fn(u64) -> u64,
+    {
+        check_message_modulus(message_modulus, poly_size)?;
+
+        let delta = message_delta(message_modulus);
+        let box_size = poly_size.0 / message_modulus;
+
+        let mut accumulator = RLWECiphertext::allocate(poly_size);
+        for (i, coeff) in accumulator.get_mut_body().as_mut_tensor().iter_mut().enumerate() {
+            let message = (i / box_size) as u64;
+            let value = f(message) % (message_modulus as u64);
+            *coeff = (value as Scalar).wrapping_mul(delta);
+        }
+        accumulator.update_with_monomial_div(MonomialDegree(box_size / 2));
+
+        Ok(LookupTable { accumulator, message_modulus })
+    }
+
+    pub fn message_modulus(&self) -> usize {
+        self.message_modulus
+    }
+
+    pub fn polynomial_size(&self) -> PolynomialSize {
+        self.accumulator.polynomial_size()
+    }
+
+    pub fn accumulator(&self) -> &RLWECiphertext {
+        &self.accumulator
+    }
+}
+
+/// A lookup table packing 2^lut_count_log functions in a single test polynomial.
+/// Every box of size N / message_modulus is split in slots of 2^lut_count_log coefficients and the
+/// j-th coefficient of every slot holds f_j(m) * Delta. Since the multi-value bootstrap only rotates
+/// by multiples of 2^lut_count_log, f_j(m) ends up in the j-th coefficient of the accumulator.
+#[derive(Debug, Clone)]
+pub struct MultiValueLookupTable {
+    pub(crate) accumulator: RLWECiphertext,
+    pub(crate) message_modulus: usize,
+    pub(crate) function_count: usize,
+    pub(crate) lut_count_log: LutCountLog,
+}
+
+impl MultiValueLookupTable {
+    pub fn new(functions: &[&dyn Fn(u64) -> u64], message_modulus: usize, poly_size: PolynomialSize) -> Result<MultiValueLookupTable, MfheError> {
+        if functions.is_empty() {
+            return Err(ContextError::NoLookupFunction.into());
+        }
+        check_message_modulus(message_modulus, poly_size)?;
+
+        let lut_count = functions.len().next_power_of_two();
+        let box_size = poly_size.0 / message_modulus;
+        // Every slot of 2^lut_count_log coefficients must fit twice in a box.
+        if box_size < 2 * lut_count {
+            return Err(ContextError::TooManyLookupFunctions(functions.len(), box_size / 2).into());
+        }
+
+        let delta = message_delta(message_modulus);
+
+        let mut accumulator = RLWECiphertext::allocate(poly_size);
+        for (i, coeff) in accumulator.get_mut_body().as_mut_tensor().iter_mut().enumerate() {
+            let message = (i / box_size) as u64;
+            // Unused slots (when the number of functions is not a power of two) are left to zero.
+            if let Some(f) = functions.get(i % lut_count) {
+                let value = f(message) % (message_modulus as u64);
+                *coeff = (value as Scalar).wrapping_mul(delta);
+            }
+        }
+        accumulator.update_with_monomial_div(MonomialDegree(box_size / 2));
+
+        Ok(MultiValueLookupTable {
+            accumulator,
+            message_modulus,
+            function_count: functions.len(),
+            lut_count_log: LutCountLog(lut_count.trailing_zeros() as usize),
+        })
+    }
+
+    pub fn message_modulus(&self) -> usize {
+        self.message_modulus
+    }
+
+    pub fn function_count(&self) -> usize {
+        self.function_count
+    }
+
+    pub fn lut_count_log(&self) -> LutCountLog {
+        self.lut_count_log
+    }
+
+    pub fn polynomial_size(&self) -> PolynomialSize {
+        self.accumulator.polynomial_size()
+    }
+}
+
+/// Bootstrap lwe_in, an encryption of m * Delta, with the test polynomial of lut.
+/// The output is an encryption of f(m) * Delta under the multiparty LWE key.
+pub fn programmable_bootstrap(lwe_in: &LWECiphertext, lut: &LookupTable, server_key: &ServerKey) -> Result<LWECiphertext, MfheError> {
+    if lut.polynomial_size() != server_key.polynomial_size() {
+        return Err(MfheError::ParameterMismatch("lookup table"));
+    }
+
+    let mut accumulator = lut.accumulator.clone();
+    let output_bootstrap = server_key.bootstrap(lwe_in, &mut accumulator)?;
+
+    Ok(extract_and_keyswitch(&output_bootstrap, server_key))
+}
+
+/// Run `programmable_bootstrap` on every ciphertext of lwe_in on the rayon thread pool. Every
+/// thread has its own accumulator and FFT buffers, the outputs are in the order of lwe_in.
+pub fn bootstrap_batch(lwe_in: &[LWECiphertext], lut: &LookupTable, server_key: &ServerKey) -> Result<Vec<LWECiphertext>, MfheError> {
+    if lut.polynomial_size() != server_key.polynomial_size() {
+        return Err(MfheError::ParameterMismatch("lookup table"));
+    }
+
+    lwe_in.par_iter().map_init(
+        || (lut.accumulator.clone(), server_key.gen_fourier_buffers()),
+        |(accumulator, buffers), ct| {
+            accumulator.fill_with_copy(&lut.accumulator);
+            let output_bootstrap = server_key.multi_value_bootstrap_with_buf(ct, accumulator, LutCountLog(0), buffers)?;
+            Ok(extract_and_keyswitch(&output_bootstrap, server_key))
+        },
+    ).collect()
+}
+
+/// Sample extract the constant coefficient of the output of a bootstrap and keyswitch it back to
+/// the LWE key.
+fn extract_and_keyswitch(output_bootstrap: &RLWECiphertext, server_key: &ServerKey) -> LWECiphertext {
+    let mut lwe_out = LWECiphertext::allocate(LweSize(output_bootstrap.polynomial_size().0 + 1));
+    constant_sample_extract(&mut lwe_out, output_bootstrap);
+
+    let mut switched_ciphertext = LWECiphertext::allocate(server_key.lwe_size());
+    server_key.keyswitch(&mut switched_ciphertext, &lwe_out);
+    switched_ciphertext
+}
+
+/// Bootstrap lwe_in, an encryption of m * Delta, once with the test polynomial of lut.
+/// The j-th output is an encryption of f_j(m) * Delta under the multiparty LWE key, all of them
+/// share the same blind rotation.
+pub fn multi_value_programmable_bootstrap(lwe_in: &LWECiphertext, lut: &MultiValueLookupTable, server_key: &ServerKey) -> Result<Vec<LWECiphertext>, MfheError> {
+    if lut.polynomial_size() != server_key.polynomial_size() {
+        return Err(MfheError::ParameterMismatch("lookup table"));
+    }
+
+    let mut accumulator = lut.accumulator.clone();
+    let output_bootstrap = server_key.multi_value_bootstrap(lwe_in, &mut accumulator, lut.lut_count_log)?;
+
+    Ok((0..lut.function_count).map(|j| {
+        let mut lwe_out = LWECiphertext::allocate(LweSize(lut.polynomial_size().0 + 1));
+        lwe_out.fill_with_sample_extract(&output_bootstrap, MonomialDegree(j));
+
+        let mut switched_ciphertext = LWECiphertext::allocate(server_key.lwe_size());
+        server_key.keyswitch(&mut switched_ciphertext, &lwe_out);
+        switched_ciphertext
+    }).collect())
+}
@@ -12,21 +12,40 @@ use ccore::math::tensor::{AsMutTensor, AsRefTensor, Tensor};
 use concrete_core::backends::core::private::crypto::encoding::Cleartext;
 use concrete_core::backends::core::private::crypto::lwe::{LweCiphertext, LweKeyswitchKey};
 use concrete_core::backends::core::private::crypto::secret::LweSecretKey;
-use num_traits::identities::{One, Zero};
+use num_traits::identities::Zero;
+use concrete_commons::numeric::CastFrom;
+use ccore::math::torus::UnsignedTorus;
 use crate::*;
-use crate::rgsw::FourierRGSWCiphertext;
+use crate::rgsw::{FourierBootstrappingKey, FourierRGSWCiphertext};
+use crate::error::MfheError;
+use crate::params::ContextError;
+use crate::noise::NoiseStats;
+use rayon::prelude::*;
 use ccore::crypto::lwe::{LweBody, LweMask};
 use concrete_commons::parameters::{LweSize};
+use std::mem::size_of;
+use get_size::GetSize;
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// A LWE ciphertext.
+/// A LWE ciphertext over the torus of T, 64 bits by default.
 /// It is a wrapper around `LweCiphertext` from concrete.
-pub struct LWECiphertext(pub(crate) LweCiphertext<Vec<Scalar>>);
+pub struct LWECiphertext<T = Scalar>(pub(crate) LweCiphertext<Vec<T>>);
 
+/// An LWE ciphertext in base 2^32, e.g. the output of `LWECiphertext::modulus_switch_to_u32`.
+pub type LWECiphertext32 = LWECiphertext<u32>;
 
-impl LWECiphertext {
-    pub fn allocate(size: LweSize) -> LWECiphertext {
-        LWECiphertext(LweCiphertext::allocate(Scalar::zero(), size))
+impl<T: UnsignedTorus> GetSize for LWECiphertext<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> LWECiphertext<T> {
+    pub fn allocate(size: LweSize) -> LWECiphertext<T> {
+        LWECiphertext(LweCiphertext::allocate(T::ZERO, size))
     }
 
     /// Return the length of the mask + 1 for the body.
@@ -34,113 +53,137 @@ impl LWECiphertext {
         self.0.lwe_size()
     }
 
-    pub fn get_body(&self) -> &LweBody<Scalar> {
+    pub fn get_body(&self) -> &LweBody<T> {
         self.0.get_body()
     }
 
-    pub fn get_mask(&self) -> LweMask<&[Scalar]> {
+    pub fn get_mask(&self) -> LweMask<&[T]> {
         self.0.get_mask()
     }
 
-    pub fn get_mut_mask(&mut self) -> LweMask<&mut [Scalar]> {
+    pub fn get_mut_mask(&mut self) -> LweMask<&mut [T]> {
         self.0.get_mut_mask()
     }
 
-    pub fn get_mut_body(&mut self) -> &mut LweBody<Scalar> {
+    pub fn get_mut_body(&mut self) -> &mut LweBody<T> {
         self.0.get_mut_body()
     }
 
     pub fn clear(&mut self) {
-        self.0.as_mut_tensor().fill_with(|| Scalar::zero());
+        self.0.as_mut_tensor().fill_with(|| T::ZERO);
     }
 
-    pub fn fill_with_sample_extract(&mut self, c: &RLWECiphertext, n_th: MonomialDegree) {
+    pub fn fill_with_sample_extract(&mut self, c: &RLWECiphertext<T>, n_th: MonomialDegree) {
         self.0.fill_with_glwe_sample_extraction(&c.0, n_th);
     }
 
-    pub fn fill_with_const_sample_extract(&mut self, c: &RLWECiphertext) {
+    pub fn fill_with_const_sample_extract(&mut self, c: &RLWECiphertext<T>) {
         self.0
             .fill_with_glwe_sample_extraction(&c.0, MonomialDegree(0));
     }
 
-    pub fn update_with_scalar_mul(&mut self, value: Scalar){
+    pub fn update_with_scalar_mul(&mut self, value: T){
         self.0.update_with_scalar_mul(Cleartext(value));
     }
 
-    pub fn update_with_add(&mut self, ct: LWECiphertext) {
+    pub fn update_with_add(&mut self, ct: LWECiphertext<T>) {
         self.0.update_with_add(&ct.0);
     }
 
-    pub fn update_with_sub(&mut self, ct: LWECiphertext) {
+    pub fn update_with_sub(&mut self, ct: LWECiphertext<T>) {
         self.0.update_with_sub(&ct.0);
     }
 
     pub fn update_with_neg(&mut self) {
         self.0.update_with_neg();
     }
-
 }
 
-
-#[derive(Debug, Clone)]
-/// An LWE ciphertext in base 2^32
-/// It is a wrapper around `lweCiphertext` from concrete.
-pub struct LWECiphertext32(pub(crate) LweCiphertext<Vec<u32>>);
-
-impl LWECiphertext32 {
-    pub fn allocate(size: LweSize) -> LWECiphertext32 {
-        LWECiphertext32(LweCiphertext::allocate(0u32, size))
-    }
-
-    /// Return the length of the mask + 1 for the body.
-    pub fn lwe_size(&self) -> LweSize {
-        self.0.lwe_size()
-    }
-
-    pub fn get_body(&self) -> &LweBody<u32> {
-        self.0.get_body()
+impl LWECiphertext {
+    /// Switch every coefficient to the modulus 2^log_modulus, see `modulus_switch`. The output
+    /// stays on the 64-bit torus: its coefficients are multiples of 2^(64 - log_modulus), so it
+    /// can be decrypted and decoded as any other ciphertext.
+    pub fn modulus_switch(&self, log_modulus: usize) -> Result<LWECiphertext, MfheError> {
+        check_log_modulus(log_modulus)?;
+        let shift = Scalar::BITS as usize - log_modulus;
+        let mut out = self.clone();
+        for x in out.0.as_mut_tensor().iter_mut() {
+            *x = if shift == Scalar::BITS as usize { 0 } else { switch_modulus(*x, log_modulus) << shift };
+        }
+        Ok(out)
     }
 
-    pub fn get_mask(&self) -> LweMask<&[u32]> {
-        self.0.get_mask()
+    /// Switch self to the modulus 2^32.
+    pub fn modulus_switch_to_u32(&self) -> LWECiphertext32 {
+        let mut out = LWECiphertext32::allocate(self.lwe_size());
+        for (o, x) in out.0.as_mut_tensor().iter_mut().zip(self.0.as_tensor().iter()) {
+            *o = switch_modulus(*x, 32) as u32;
+        }
+        out
     }
 
-    pub fn get_mut_mask(&mut self) -> LweMask<&mut [u32]> {
-        self.0.get_mut_mask()
-    }
+}
 
-    pub fn get_mut_body(&mut self) -> &mut LweBody<u32> {
-        self.0.get_mut_body()
-    }
+/// Round input from the modulus 2^64 to the modulus 2^log_modulus, i.e. compute
+/// round(input * 2^log_modulus / 2^64) mod 2^log_modulus.
+pub fn modulus_switch(input: Scalar, log_modulus: usize) -> Result<Scalar, MfheError> {
+    check_log_modulus(log_modulus)?;
+    Ok(switch_modulus(input, log_modulus))
+}
 
-    pub fn clear(&mut self) {
-        self.0.as_mut_tensor().fill_with(|| 0u32);
+fn check_log_modulus(log_modulus: usize) -> Result<(), MfheError> {
+    if log_modulus > Scalar::BITS as usize {
+        return Err(ContextError::ModulusTooLarge(log_modulus).into());
     }
+    Ok(())
+}
 
-    pub fn update_with_scalar_mul(&mut self, value: u32){
-        self.0.update_with_scalar_mul(Cleartext(value));
+/// Same as `modulus_switch` for a log_modulus already checked to be at most 64.
+fn switch_modulus(input: Scalar, log_modulus: usize) -> Scalar {
+    let bits = Scalar::BITS as usize;
+    if log_modulus == bits {
+        return input;
     }
-    pub fn update_with_add(&mut self, ct: LWECiphertext32) {
-        self.0.update_with_add(&ct.0);
-    }
-
-    pub fn update_with_sub(&mut self, ct: LWECiphertext32) {
-        self.0.update_with_sub(&ct.0);
+    if log_modulus == 0 {
+        return 0;
     }
+    // keep one more bit than needed to round
+    let output = (input >> (bits - log_modulus - 1)).wrapping_add(1) >> 1;
+    output & ((1 << log_modulus) - 1)
+}
 
-    pub fn update_with_neg(&mut self) {
-        self.0.update_with_neg();
-    }
 
+#[derive(Debug, Clone, Copy)]
+/// The share of one party for the threshold decryption of a LWE ciphertext, see
+/// `LWESecretKey::partial_decrypt`.
+pub struct DecryptionShare<T = Scalar> {
+    pub party_id: usize,
+    pub(crate) value: T,
 }
 
+/// Recover the (noisy) plaintext of ct from the decryption shares of all the k parties:
+/// b - sum_i (<a, s_i> + e_i) = Delta*m + e - sum_i e_i.
+/// A missing share would leave the key of its party in the output, so there must be exactly one
+/// share per party id in [0, k).
+pub fn combine_shares<T: UnsignedTorus>(ct: &LWECiphertext<T>, shares: &[DecryptionShare<T>], k: usize) -> Result<Plaintext<T>, MfheError> {
+    check_party_ids("list of decryption shares", shares.iter().map(|share| share.party_id), k)?;
+    let sum = shares.iter().fold(T::ZERO, |ac, share| ac.wrapping_add(share.value));
+    Ok(Plaintext(ct.get_body().0.wrapping_sub(sum)))
+}
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// A LWE secret key
+/// A LWE secret key over the torus of T, its coefficients are binary.
 /// It is a wrapper around `LweSecretKey` from concrete.
-pub struct LWESecretKey(pub(crate) LweSecretKey<BinaryKeyKind, Vec<Scalar>>);
+pub struct LWESecretKey<T = Scalar>(pub(crate) LweSecretKey<BinaryKeyKind, Vec<T>>);
 
-impl LWESecretKey {
+impl<T: UnsignedTorus> GetSize for LWESecretKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> LWESecretKey<T> {
     /// Generate a secret key where the coefficients are binary.
     pub fn generate_binary(
         lwe_dimension: LweDimension,
@@ -151,8 +194,8 @@ impl LWESecretKey {
 
     pub fn encrypt_lwe(
         &self,
-        output: &mut LWECiphertext,
-        pt: &Plaintext<Scalar>,
+        output: &mut LWECiphertext<T>,
+        pt: &Plaintext<T>,
         noise_parameters: impl DispersionParameter,
         generator: &mut EncryptionRandomGenerator,
     ) {
@@ -160,44 +203,84 @@ impl LWESecretKey {
             .encrypt_lwe(&mut output.0, pt, noise_parameters, generator);
     }
 
-    pub fn binary_encrypt_lwe(
-        &self,
-        output: &mut LWECiphertext,
-        pt: &Plaintext<Scalar>,
-        ctx: &mut Context,
-    ) {
-        let mut encoded_pt = pt.clone();
-        binary_encode(&mut encoded_pt.0);
-        self.encrypt_lwe(output, &encoded_pt, ctx.std, &mut ctx.encryption_generator);
-    }
-
-    pub fn decrypt_lwe(&self, output: &mut Plaintext<Scalar>, ct: &LWECiphertext) {
-        self.0.decrypt_lwe(output, &ct.0);
+    /// Decrypt a ciphertext over the torus of U, e.g. the output of
+    /// `LWECiphertext::modulus_switch_to_u32`. The coefficients of the key are cast to U.
+    pub fn decrypt_lwe<U>(&self, output: &mut Plaintext<U>, ct: &LWECiphertext<U>)
+    where U: UnsignedTorus + CastFrom<T> {
+        let sub = ct.get_mask().as_tensor().fold_with_one(
+            self.0.as_tensor(),
+            U::ZERO,
+            |ac, a_i, s_i| ac.wrapping_add(a_i.wrapping_mul(U::cast_from(*s_i))),
+        );
+        output.0 = ct.get_body().0.wrapping_sub(sub);
     }
 
     pub fn decrypt_wrapping_lwe(
         &self,
-        pt: &mut Plaintext<Scalar>,
-        encrypted: &LWECiphertext,
+        pt: &mut Plaintext<T>,
+        encrypted: &LWECiphertext<T>,
     ) {
         
         self.0.decrypt_wrapping_lwe(pt, &encrypted.0);
     }
 
-    /// Decrypt a LWE ciphertext and then decode.
-    pub fn binary_decrypt_lwe(&self, pt: &mut Plaintext<Scalar>, encrypted: &LWECiphertext) {
-        self.decrypt_lwe(pt, encrypted);
-        binary_decode(&mut pt.0);
+    /// Compute the share <a, s_i> + e of the party with id party_id for the threshold decryption
+    /// of ct. The smudging noise e hides the key of the party in the share, it must be much larger
+    /// than the noise of ct (but small enough to keep the message decodable).
+    pub fn partial_decrypt(
+        &self,
+        ct: &LWECiphertext<T>,
+        party_id: usize,
+        smudging_noise: impl DispersionParameter,
+        generator: &mut RandomGenerator,
+    ) -> DecryptionShare<T> {
+        let mask_times_key = ct.get_mask().as_tensor().fold_with_one(
+            self.as_tensor(),
+            T::ZERO,
+            |ac, a_i, s_i| ac.wrapping_add(a_i.wrapping_mul(*s_i)),
+        );
+        let (noise, _): (T, T) = generator.random_gaussian(0., smudging_noise.get_standard_dev());
+        DecryptionShare { party_id, value: mask_times_key.wrapping_add(noise) }
+    }
+
+    /// Encrypt every coefficient of the key as a RGSW ciphertext under the joint RLWE public key.
+    /// This is the contribution of a party to the bootstrapping key, see `generate_bootstrapping_key`.
+    pub fn encrypt_rgsw_bits(&self, global_rlwe_pk: &RLWEPublicKey<T>, ctx: &mut Context) -> Result<Vec<RGSWCiphertext<T>>, MfheError> {
+        self.0.as_tensor().iter().map(|bit| {
+            let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
+            global_rlwe_pk.encrypt_constant_rgsw(&mut ct, &Plaintext(*bit), ctx)?;
+            Ok(ct)
+        }).collect()
+    }
+
+    /// Same as `encrypt_rgsw_bits`, the coefficients are encrypted in parallel. Every coefficient
+    /// is encrypted with its own context forked from ctx, so the output only depends on the seeds
+    /// of ctx and not on the number of threads.
+    pub fn par_encrypt_rgsw_bits(&self, global_rlwe_pk: &RLWEPublicKey<T>, ctx: &mut Context) -> Result<Vec<RGSWCiphertext<T>>, MfheError> where T: Send + Sync {
+        let (base_log, level_count) = (ctx.rgsw_base_log, ctx.rgsw_level_count);
+        self.par_encrypt_rgsw_bits_with_decomposition(global_rlwe_pk, base_log, level_count, ctx)
+    }
+
+    /// Same as `par_encrypt_rgsw_bits` with the given gadget decomposition instead of the RGSW one
+    /// of the context, e.g. the keyswitching one for a `LWEtoRLWEKeyswitchKey`.
+    pub fn par_encrypt_rgsw_bits_with_decomposition(&self, global_rlwe_pk: &RLWEPublicKey<T>, base_log: DecompositionBaseLog,
+        level_count: DecompositionLevelCount, ctx: &mut Context) -> Result<Vec<RGSWCiphertext<T>>, MfheError> where T: Send + Sync {
+        let mut forks: Vec<Context> = self.0.as_tensor().iter().map(|_| ctx.fork()).collect();
+        self.0.as_tensor().as_container().par_iter().zip(forks.par_iter_mut()).map(|(bit, fork)| {
+            let mut ct = RGSWCiphertext::allocate(fork.poly_size, base_log, level_count);
+            global_rlwe_pk.encrypt_constant_rgsw(&mut ct, &Plaintext(*bit), fork)?;
+            Ok(ct)
+        }).collect()
     }
 
-    pub fn to_rlwe_sk(&self) -> RLWESecretKey {
+    pub fn to_rlwe_sk(&self) -> RLWESecretKey<T> {
         let mut sk = RLWESecretKey::zero(PolynomialSize(self.0.key_size().0));
         sk.fill_with_copy(self.0.as_tensor());
         sk
     }
 
     pub fn from_concrete_sk(
-        concrete_sk: LweSecretKey<BinaryKeyKind,Vec<Scalar>>) -> LWESecretKey {
+        concrete_sk: LweSecretKey<BinaryKeyKind,Vec<T>>) -> LWESecretKey<T> {
             LWESecretKey(concrete_sk)
     }
 
@@ -205,28 +288,59 @@ impl LWESecretKey {
         self.0.key_size()
     }
 
-    pub fn as_mut_tensor(&mut self) -> &mut Tensor<Vec<Scalar>>{
+    pub fn as_mut_tensor(&mut self) -> &mut Tensor<Vec<T>>{
         self.0.as_mut_tensor()
     }
 
-    pub fn as_tensor(& self) -> &Tensor<Vec<Scalar>>{
+    pub fn as_tensor(& self) -> &Tensor<Vec<T>>{
         self.0.as_tensor()
     }
 
     pub fn zero(glwe_size: usize) -> Self {
         LWESecretKey(
-            LweSecretKey::binary_from_container(vec![Scalar::zero(); glwe_size])
+            LweSecretKey::binary_from_container(vec![T::ZERO; glwe_size])
         )
     }
 
-    pub fn generate_mask(&self, encrypted: &mut LWEPublicKey, ctx: &mut Context) {
-        for i in 0..ctx.m {
-            self.0.fill_mask(encrypted.0.get_mut(i).unwrap(), &mut ctx.encryption_generator);
+    /// Fill the masks of the m samples of encrypted from the mask stream of generator.
+    pub fn generate_mask(&self, encrypted: &mut LWEPublicKey<T>, generator: &mut EncryptionRandomGenerator) {
+        for ct in encrypted.0.iter_mut() {
+            self.0.fill_mask(ct, generator);
+        }
+    }
+
+    /// Generate the common masks of a keyswitching key that is built by several parties.
+    pub fn generate_ksk_mask(&self, ksk: &mut LWEKeyswitchKey<T>, generator: &mut EncryptionRandomGenerator) {
+        let lwe_size = ksk.0.lwe_size().0;
+        for chunk in ksk.0.as_mut_tensor().subtensor_iter_mut(lwe_size) {
+            let mut ct = LweCiphertext::from_container(chunk.into_container());
+            self.0.fill_mask(&mut ct, generator);
+        }
+    }
+
+    /// Fill the bodies of share (a copy of the keyswitching key holding the common masks) with the
+    /// contribution of this party: for the input coefficient t and the level l, the body is
+    /// <a, s_i> + e + input_key[t] * q / B^l, where s_i is self and input_key is the extracted
+    /// RLWE key of the party. Summing the shares of all the parties gives a keyswitching key from
+    /// the joint extracted key to the joint LWE key.
+    pub fn fill_ksk_share(&self, share: &mut LWEKeyswitchKey<T>, input_key: &LWESecretKey<T>,
+        noise_parameter: impl DispersionParameter, generator: &mut EncryptionRandomGenerator) {
+        let lwe_size = share.0.lwe_size().0;
+        let base_log = share.0.decomposition_base_log().0;
+        let level_count = share.0.decomposition_levels_count().0;
+        for (i, chunk) in share.0.as_mut_tensor().subtensor_iter_mut(lwe_size).enumerate() {
+            let mut ct = LweCiphertext::from_container(chunk.into_container());
+            self.0.encrypt_lwe_mask(&mut ct, noise_parameter, generator);
+
+            let input_bit = *input_key.as_tensor().get_element(i / level_count);
+            let level = (i % level_count) + 1;
+            let message = input_bit << (T::BITS - base_log * level);
+            ct.get_mut_body().0 = ct.get_body().0.wrapping_add(message);
         }
     }
 
     /// fills the mask of self with the mask of encrypted
-    pub fn fill_lwe_pk(&self, encrypted: &mut LWEPublicKey,
+    pub fn fill_lwe_pk(&self, encrypted: &mut LWEPublicKey<T>,
         noise_parameter: impl DispersionParameter, generator: &mut EncryptionRandomGenerator, m: usize) {
         for i in 0..m {
             self.0.encrypt_lwe_mask(&mut encrypted.0.get_mut(i).unwrap(), noise_parameter, generator);
@@ -234,14 +348,42 @@ impl LWESecretKey {
     }
 
 }
+
+impl LWESecretKey {
+    pub fn binary_encrypt_lwe(
+        &self,
+        output: &mut LWECiphertext,
+        pt: &Plaintext<Scalar>,
+        ctx: &mut Context,
+    ) -> Result<(), MfheError> {
+        let mut encoded_pt = pt.clone();
+        binary_encode(&mut encoded_pt.0)?;
+        self.encrypt_lwe(output, &encoded_pt, ctx.std, &mut ctx.encryption_generator);
+        Ok(())
+    }
+
+    /// Decrypt a LWE ciphertext and then decode.
+    pub fn binary_decrypt_lwe(&self, pt: &mut Plaintext<Scalar>, encrypted: &LWECiphertext) {
+        self.decrypt_lwe(pt, encrypted);
+        binary_decode(&mut pt.0);
+    }
+}
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-pub struct LWEPublicKey(pub(crate) Vec<LweCiphertext<Vec<Scalar>>>);
+pub struct LWEPublicKey<T = Scalar>(pub(crate) Vec<LweCiphertext<Vec<T>>>);
+
+impl<T: UnsignedTorus> GetSize for LWEPublicKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.iter().map(|ct| ct.as_tensor().len() * size_of::<T>()).sum::<usize>()
+            + self.0.capacity() * size_of::<LweCiphertext<Vec<T>>>()
+    }
+}
 
-impl LWEPublicKey {
-    pub fn allocate(n: usize, m: usize) -> LWEPublicKey {
-        let mut lwe_list: Vec<LweCiphertext<Vec<Scalar>>> = Vec::new();
+impl<T: UnsignedTorus> LWEPublicKey<T> {
+    pub fn allocate(n: usize, m: usize) -> LWEPublicKey<T> {
+        let mut lwe_list: Vec<LweCiphertext<Vec<T>>> = Vec::new();
         for _i in 0..m {
-            let ct = LweCiphertext::from_container(vec![Scalar::zero(); n]);
+            let ct = LweCiphertext::from_container(vec![T::ZERO; n]);
             lwe_list.push(ct);
         }
         LWEPublicKey(lwe_list)
@@ -250,15 +392,38 @@ impl LWEPublicKey {
     pub fn get_size(&self) -> usize {
         self.0.len()
     }
+
+    /// Fill the masks of self from the mask stream of a generator seeded with seed.
+    pub(crate) fn fill_mask_from_seed(&mut self, seed: u128) {
+        let lwe_size = self.0.first().map_or(1, |ct| ct.lwe_size().0);
+        let mut generator = EncryptionRandomGenerator::new(Some(seed));
+        LWESecretKey::<T>::zero(lwe_size.saturating_sub(1)).generate_mask(self, &mut generator);
+    }
+
+    /// Keep only the bodies of self. The masks must have been expanded from seed (see
+    /// `CommonReferenceString`), they are regenerated by `CompressedLWEPublicKey::decompress`.
+    pub fn compress(&self, seed: u128) -> CompressedLWEPublicKey<T> {
+        let lwe_size = self.0.first().map_or(1, |ct| ct.lwe_size().0);
+        debug_assert!({
+            let mut expanded = LWEPublicKey::<T>::allocate(lwe_size, self.get_size());
+            expanded.fill_mask_from_seed(seed);
+            expanded.compare_mask(self, self.get_size())
+        }, "the masks of the public key are not expanded from the seed");
+        CompressedLWEPublicKey {
+            seed,
+            lwe_size,
+            bodies: self.0.iter().map(|ct| ct.get_body().0).collect(),
+        }
+    }
     
-    pub fn update_with_global_mask(&mut self, global_pk: &LWEPublicKey, ctx: &Context) {
+    pub fn update_with_global_mask(&mut self, global_pk: &LWEPublicKey<T>, ctx: &Context) {
         for i in 0..ctx.m {
             self.0.get_mut(i).unwrap().get_mut_mask().as_mut_tensor().fill_with_copy(global_pk.0.get(i).unwrap().get_mask().as_tensor());
             //println!("update_with_global_mask: {:?}",global_pk.0.get(i).unwrap().get_mask().as_tensor().len());
         }
     }
 
-    pub fn update_body_with_pk(&mut self, pk: &LWEPublicKey, ctx: &Context) {
+    pub fn update_body_with_pk(&mut self, pk: &LWEPublicKey<T>, ctx: &Context) {
         
         for i in 0..ctx.m {
             //println!("update_body_with_pk {:?}", self.0.get_mut(i).unwrap().get_mut_body().0);
@@ -269,8 +434,8 @@ impl LWEPublicKey {
         }
     }
 
-    pub fn get_body(&mut self, m: usize) -> Vec<Scalar> {
-        let mut body: Vec<Scalar> = Vec::new();
+    pub fn get_body(&mut self, m: usize) -> Vec<T> {
+        let mut body: Vec<T> = Vec::new();
         for i in 0..m {
             body.push(self.0.get_mut(i).unwrap().get_body().0);
 
@@ -278,25 +443,28 @@ impl LWEPublicKey {
         body
     }
 
-    pub fn encrypt_lwe(&self, encrypted: &mut LWECiphertext, pt: &Plaintext<Scalar>, generator: &mut SecretRandomGenerator, m: usize) {
+    /// Encrypt pt with a random subset sum of the m samples of the public key.
+    pub fn encrypt_lwe(&self, encrypted: &mut LWECiphertext<T>, pt: &Plaintext<T>, generator: &mut SecretRandomGenerator, m: usize) -> Result<(), MfheError> {
 
-        assert_eq!(m,self.get_size());
+        if m != self.get_size() {
+            return Err(MfheError::DimensionMismatch { what: "LWE public key", expected: m, found: self.get_size() });
+        }
 
         //println!("Encrypted before: {:?}", encrypted);
 
-        if m == Scalar::one() as usize{
+        if m == 1 {
             encrypted.0.get_mut_mask().as_mut_tensor().update_with_wrapping_add(self.0.get(0).unwrap().get_mask().as_tensor());
             //encrypted.0.get_mut_body().as_mut_polynomial().update_with_wrapping_add(&self.0.get(0).unwrap().get_body().as_polynomial());
             let sum = encrypted.0.get_body().0.wrapping_add(self.0.get(0).unwrap().get_body().0); 
             encrypted.0.get_mut_body().0 = sum;
         } else {
-            let r = LWESecretKey::generate_binary(LweDimension(self.get_size() - 1), generator);
+            let r = LWESecretKey::<T>::generate_binary(LweDimension(self.get_size() - 1), generator);
             //println!("Random vector = {:?}", r.as_tensor());
         
-            let mut sum = Scalar::zero();
+            let mut sum = T::ZERO;
             
             for (i, val) in r.0.as_tensor().iter().enumerate() {
-                if *val == Scalar::one() {
+                if *val == T::ONE {
                     encrypted.0.get_mut_mask().as_mut_tensor().update_with_wrapping_add(self.0.get(i).unwrap().get_mask().as_tensor());
                     sum = sum.wrapping_add(encrypted.0.get_body().0.wrapping_add(self.0.get(i).unwrap().get_body().0)); 
                 }
@@ -313,13 +481,16 @@ impl LWEPublicKey {
 
         //println!("Encrypted after adding body: {:?}", encrypted);
 
+        Ok(())
     }
 
 
-    pub fn compare_mask(&self, pk: &LWEPublicKey, m: usize) {
-        for i in 0..m {
-            assert_eq!(self.0.get(i).unwrap().get_mask(),pk.0.get(i).unwrap().get_mask());
-        }
+    /// Return true if the first m samples of self and pk hold the same masks.
+    pub fn compare_mask(&self, pk: &LWEPublicKey<T>, m: usize) -> bool {
+        (0..m).all(|i| match (self.0.get(i), pk.0.get(i)) {
+            (Some(a), Some(b)) => a.get_mask().as_tensor() == b.get_mask().as_tensor(),
+            _ => false,
+        })
     }
 
     pub fn get_numberof_mask(&self) -> usize {
@@ -330,30 +501,178 @@ impl LWEPublicKey {
 
 }
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+/// An LWE public key stored as the seed of its masks and its m bodies, about n times smaller
+/// than `LWEPublicKey`.
+pub struct CompressedLWEPublicKey<T = Scalar> {
+    seed: u128,
+    lwe_size: usize,
+    bodies: Vec<T>,
+}
+
+impl<T: UnsignedTorus> GetSize for CompressedLWEPublicKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.bodies.len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> CompressedLWEPublicKey<T> {
+    pub fn seed(&self) -> u128 {
+        self.seed
+    }
+
+    /// Regenerate the masks from the seed. The sizes come from a message, they are checked against
+    /// the ones of ctx (n + 1 and m) before anything is allocated.
+    pub fn decompress(&self, ctx: &Context) -> Result<LWEPublicKey<T>, MfheError> {
+        if self.lwe_size != ctx.glwe_size.0 {
+            return Err(MfheError::DimensionMismatch { what: "compressed LWE public key", expected: ctx.glwe_size.0, found: self.lwe_size });
+        }
+        if self.bodies.len() != ctx.m {
+            return Err(MfheError::DimensionMismatch { what: "list of LWE public key bodies", expected: ctx.m, found: self.bodies.len() });
+        }
+        let mut pk = LWEPublicKey::allocate(self.lwe_size, self.bodies.len());
+        pk.fill_mask_from_seed(self.seed);
+        for (ct, body) in pk.0.iter_mut().zip(self.bodies.iter()) {
+            ct.get_mut_body().0 = *body;
+        }
+        Ok(pk)
+    }
+}
+
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An LWE secret key.
-pub struct LWEKeyswitchKey(pub(crate) LweKeyswitchKey<Vec<Scalar>>);
+/// An LWE keyswitching key over the torus of T.
+pub struct LWEKeyswitchKey<T = Scalar>(pub(crate) LweKeyswitchKey<Vec<T>>);
 
-impl LWEKeyswitchKey {
+impl<T: UnsignedTorus> GetSize for LWEKeyswitchKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
+    }
+}
 
-    pub fn allocate(input_size: LweDimension, output_size: LweDimension, ctx: &Context) -> LWEKeyswitchKey {
-        LWEKeyswitchKey(LweKeyswitchKey::allocate(0 as Scalar,ctx.ks_level_count,ctx.ks_base_log,input_size,output_size))
+impl<T: UnsignedTorus> LWEKeyswitchKey<T> {
+
+    pub fn allocate(input_size: LweDimension, output_size: LweDimension, ctx: &Context) -> LWEKeyswitchKey<T> {
+        LWEKeyswitchKey(LweKeyswitchKey::allocate(T::ZERO,ctx.ks_level_count,ctx.ks_base_log,input_size,output_size))
     }
 
-    pub fn fill_with_keyswitching_key(&mut self, input_key: &LWESecretKey, output_key: &LWESecretKey, ctx: &mut Context) {
+    pub fn fill_with_keyswitching_key(&mut self, input_key: &LWESecretKey<T>, output_key: &LWESecretKey<T>, ctx: &mut Context) {
         self.0.fill_with_keyswitch_key(&input_key.0, &output_key.0, ctx.std_rlwe, &mut ctx.encryption_generator);
     }
     
-    pub fn keyswitch_ciphertext(&self, after: &mut LWECiphertext, before: &LWECiphertext) {
+    pub fn keyswitch_ciphertext(&self, after: &mut LWECiphertext<T>, before: &LWECiphertext<T>) {
         self.0.keyswitch_ciphertext(&mut after.0, &before.0);
     }
 
+    /// Add the bodies of the share of a party to the bodies of self, both must hold the same masks.
+    pub fn update_body_with_share(&mut self, share: &LWEKeyswitchKey<T>) {
+        let lwe_size = self.0.lwe_size().0;
+        for (mut chunk, share_chunk) in self.0.as_mut_tensor().subtensor_iter_mut(lwe_size)
+            .zip(share.0.as_tensor().subtensor_iter(lwe_size)) {
+            let body = chunk.last_mut();
+            *body = body.wrapping_add(*share_chunk.last());
+        }
+    }
+
+    /// Fill the masks of self from the mask stream of a generator seeded with seed.
+    pub(crate) fn fill_mask_from_seed(&mut self, seed: u128) {
+        let mut generator = EncryptionRandomGenerator::new(Some(seed));
+        LWESecretKey::<T>::zero(self.0.after_key_size().0).generate_ksk_mask(self, &mut generator);
+    }
+
+    /// Keep only the bodies of self. The masks must have been expanded from seed (see
+    /// `CommonReferenceString`), they are regenerated by `CompressedLWEKeyswitchKey::decompress`.
+    pub fn compress(&self, seed: u128) -> CompressedLWEKeyswitchKey<T> {
+        let compressed = CompressedLWEKeyswitchKey {
+            seed,
+            input_size: self.0.before_key_size().0,
+            output_size: self.0.after_key_size().0,
+            base_log: self.0.decomposition_base_log().0,
+            level_count: self.0.decomposition_levels_count().0,
+            bodies: self.0.as_tensor().subtensor_iter(self.0.lwe_size().0).map(|ct| *ct.last()).collect(),
+        };
+        debug_assert!(compressed.expand().compare_mask(self), "the masks of the keyswitching key are not expanded from the seed");
+        compressed
+    }
+
+    /// Return true if self and other hold the same masks (the bodies are not compared).
+    pub fn compare_mask(&self, other: &LWEKeyswitchKey<T>) -> bool {
+        let lwe_size = self.0.lwe_size().0;
+        self.0.as_tensor().len() == other.0.as_tensor().len()
+            && self.0.as_tensor().subtensor_iter(lwe_size)
+                .zip(other.0.as_tensor().subtensor_iter(lwe_size))
+                .all(|(a, b)| a.get_sub(..lwe_size - 1) == b.get_sub(..lwe_size - 1))
+    }
+
+}
+
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+/// An LWE keyswitching key stored as the seed of its masks and its bodies, about n times smaller
+/// than `LWEKeyswitchKey`.
+pub struct CompressedLWEKeyswitchKey<T = Scalar> {
+    seed: u128,
+    input_size: usize,
+    output_size: usize,
+    base_log: usize,
+    level_count: usize,
+    bodies: Vec<T>,
+}
+
+impl<T: UnsignedTorus> GetSize for CompressedLWEKeyswitchKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.bodies.len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> CompressedLWEKeyswitchKey<T> {
+    pub fn seed(&self) -> u128 {
+        self.seed
+    }
+
+    /// Regenerate the masks from the seed. The sizes and the decomposition come from a message,
+    /// they are checked against the ones of ctx (N to n with the keyswitching decomposition)
+    /// before anything is allocated.
+    pub fn decompress(&self, ctx: &Context) -> Result<LWEKeyswitchKey<T>, MfheError> {
+        if self.input_size != ctx.poly_size.0 {
+            return Err(MfheError::DimensionMismatch { what: "compressed keyswitching key input", expected: ctx.poly_size.0, found: self.input_size });
+        }
+        if self.output_size != ctx.glwe_size.0 - 1 {
+            return Err(MfheError::DimensionMismatch { what: "compressed keyswitching key output", expected: ctx.glwe_size.0 - 1, found: self.output_size });
+        }
+        if self.base_log != ctx.ks_base_log.0 || self.level_count != ctx.ks_level_count.0 {
+            return Err(MfheError::ParameterMismatch("compressed keyswitching key"));
+        }
+        let body_count = self.input_size * self.level_count;
+        if self.bodies.len() != body_count {
+            return Err(MfheError::DimensionMismatch { what: "list of keyswitching key bodies", expected: body_count, found: self.bodies.len() });
+        }
+        Ok(self.expand())
+    }
+
+    /// Same as `decompress` without checking the sizes.
+    fn expand(&self) -> LWEKeyswitchKey<T> {
+        let mut ksk = LWEKeyswitchKey(LweKeyswitchKey::allocate(
+            T::ZERO,
+            DecompositionLevelCount(self.level_count),
+            DecompositionBaseLog(self.base_log),
+            LweDimension(self.input_size),
+            LweDimension(self.output_size),
+        ));
+        ksk.fill_mask_from_seed(self.seed);
+        let lwe_size = ksk.0.lwe_size().0;
+        for (mut ct, body) in ksk.0.as_mut_tensor().subtensor_iter_mut(lwe_size).zip(self.bodies.iter()) {
+            *ct.last_mut() = *body;
+        }
+        ksk
+    }
 }
 
-pub fn constant_sample_extract<>(
-    lwe: &mut LWECiphertext,
-    glwe: &RLWECiphertext,
+pub fn constant_sample_extract<T: UnsignedTorus>(
+    lwe: &mut LWECiphertext<T>,
+    glwe: &RLWECiphertext<T>,
 ) {
     // We extract the mask  and body of both ciphertexts
     let (mut body_lwe, mut mask_lwe) = lwe.0.get_mut_body_and_mask();
@@ -377,7 +696,7 @@ pub fn constant_sample_extract<>(
             .coefficient_iter_mut()
             .zip(mask_glwe_polynomial.coefficient_iter().rev())
         {
-            *lwe_coeff = Scalar::zero().wrapping_sub(*glwe_coeff);
+            *lwe_coeff = T::ZERO.wrapping_sub(*glwe_coeff);
         }
     }
 
@@ -390,16 +709,23 @@ pub fn constant_sample_extract<>(
 
 
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
 /// An LWE to RLWE key switching key.
-pub struct LWEtoRLWEKeyswitchKey {
+pub struct LWEtoRLWEKeyswitchKey<T = Scalar> {
     // TODO At the moment it's a list of full RGSW ciphertexts,
     // we should remove half of the rows.
-    pub(crate) inner: Vec<RGSWCiphertext>,
+    pub(crate) inner: Vec<RGSWCiphertext<T>>,
+}
+
+impl<T: UnsignedTorus> GetSize for LWEtoRLWEKeyswitchKey<T> {
+    fn get_heap_size(&self) -> usize {
+        GetSize::get_heap_size(&self.inner)
+    }
 }
 
-impl LWEtoRLWEKeyswitchKey {
-    pub fn allocate(ctx: &Context) -> LWEtoRLWEKeyswitchKey {
+impl<T: UnsignedTorus> LWEtoRLWEKeyswitchKey<T> {
+    pub fn allocate(ctx: &Context) -> LWEtoRLWEKeyswitchKey<T> {
         LWEtoRLWEKeyswitchKey {
             inner: vec![
                 RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
@@ -408,7 +734,7 @@ impl LWEtoRLWEKeyswitchKey {
         }
     }
 
-    pub fn fill_with_keyswitching_key(&mut self, sk: &LWESecretKey, ctx: &mut Context) {
+    pub fn fill_with_keyswitching_key(&mut self, sk: &LWESecretKey<T>, ctx: &mut Context) {
         assert_eq!(ctx.poly_size.0, sk.key_size().0);
         let rlwe_sk = sk.to_rlwe_sk();
         self.inner = vec![];
@@ -420,13 +746,35 @@ impl LWEtoRLWEKeyswitchKey {
             self.inner.push(rgsw_ct);
         }
     }
+
+    /// Build the key from the RGSW encryptions of the LWE key bits of every party under the joint
+    /// RLWE public key (see `LWESecretKey::par_encrypt_rgsw_bits_with_decomposition`): the sum of
+    /// the encryptions of the i-th bits encrypts the i-th coefficient of the joint LWE key.
+    pub fn from_shares(shares: &[&[RGSWCiphertext<T>]]) -> Result<LWEtoRLWEKeyswitchKey<T>, MfheError> {
+        let (first, others) = shares.split_first().ok_or(ContextError::NoParties)?;
+        if let Some(share) = others.iter().find(|share| share.len() != first.len()) {
+            return Err(MfheError::DimensionMismatch { what: "keyswitching key share", expected: first.len(), found: share.len() });
+        }
+        let mut inner = first.to_vec();
+        for share in others {
+            for (ct, other) in inner.iter_mut().zip(share.iter()) {
+                ct.add_ciphertext(other);
+            }
+        }
+        Ok(LWEtoRLWEKeyswitchKey { inner })
+    }
+
+    /// Return the dimension of the LWE ciphertexts the key converts.
+    pub fn lwe_dimension(&self) -> usize {
+        self.inner.len()
+    }
 }
 
-pub fn conv_lwe_to_rlwe(
-    ksks: &LWEtoRLWEKeyswitchKey,
-    lwe: &LWECiphertext,
+pub fn conv_lwe_to_rlwe<T: UnsignedTorus>(
+    ksks: &LWEtoRLWEKeyswitchKey<T>,
+    lwe: &LWECiphertext<T>,
     ctx: &Context,
-) -> RLWECiphertext {
+) -> RLWECiphertext<T> {
     let mut out = RLWECiphertext::allocate(ctx.poly_size);
 
     for (ksk, a) in ksks.inner.iter().zip(lwe.get_mask().as_tensor().iter()) {
@@ -472,7 +820,7 @@ pub fn conv_lwe_to_rlwe(
     }
 
     let b_poly = {
-        let mut v = vec![Scalar::zero(); ctx.poly_size.0];
+        let mut v = vec![T::ZERO; ctx.poly_size.0];
         v[0] = lwe.get_body().0;
         Polynomial::from_container(v)
     };
@@ -483,20 +831,16 @@ pub fn conv_lwe_to_rlwe(
     out
 }
 
-pub fn compute_noise_lwe(sk: &LWESecretKey, ct: &LWECiphertext, encoded_ptxt: Scalar) -> f64 
+/// Compute the statistics of the error of ct, where encoded_ptxt is the expected encoding (see
+/// `NoiseStats`, count is 1).
+pub fn compute_noise_lwe(sk: &LWESecretKey, ct: &LWECiphertext, encoded_ptxt: Scalar, threshold: Scalar) -> NoiseStats
 {
     // pt = b - a*s = Delta*m + e
     let mut pt = Plaintext(Scalar::zero());
     sk.decrypt_wrapping_lwe(&mut pt, ct);
 
-    //println!("Delta m + e = {:?}",pt);
-    //println!("Encoding: {:?}", encoded_ptxt);
-
     // pt = pt - Delta*m = e (encoded_ptxt is Delta*m)
-    let error = pt.0.wrapping_sub(encoded_ptxt) as SignedScalar;
-
-    //println!("Error: {:?}", error);
-    ((error as SignedScalar).abs() as f64).log2()
+    NoiseStats::from_errors(std::iter::once(pt.0.wrapping_sub(encoded_ptxt)), threshold)
 }
 
 
@@ -612,109 +956,177 @@ pub fn bootstrap_debug (
 }
 
 //Function to bootstrap a LWE ciphertext.
-pub fn bootstrap (
-    lwe_in: &LWECiphertext,
-    accumulator: &mut RLWECiphertext,
-    bootstrappingkeys: Vec<Vec<RGSWCiphertext>>,
-    mut ctx: &mut Context,
-) -> RLWECiphertext
+pub fn bootstrap<T: UnsignedTorus> (
+    lwe_in: &LWECiphertext<T>,
+    accumulator: &mut RLWECiphertext<T>,
+    bootstrappingkeys: &[Vec<RGSWCiphertext<T>>],
+    ctx: &Context,
+) -> Result<RLWECiphertext<T>, MfheError>
+{
+    let mut buffers = ctx.gen_fourier_buffers();
+    bootstrap_with_buf(lwe_in, accumulator, bootstrappingkeys, &mut buffers)
+}
+
+//Function to bootstrap a LWE ciphertext reusing the buffers used for FFT.
+pub fn bootstrap_with_buf<T: UnsignedTorus> (
+    lwe_in: &LWECiphertext<T>,
+    accumulator: &mut RLWECiphertext<T>,
+    bootstrappingkeys: &[Vec<RGSWCiphertext<T>>],
+    buffers: &mut FourierBuffers<T>,
+) -> Result<RLWECiphertext<T>, MfheError>
 {
+    let poly_size = accumulator.polynomial_size();
 
     let (lwe_body, lwe_mask) = lwe_in.0.get_body_and_mask();
 
     let rotation = pbs_modulus_switch(
         lwe_body.0,
-        ctx.poly_size,
+        poly_size,
         ModulusSwitchOffset(0),
         LutCountLog(0));
 
     accumulator.0.as_mut_polynomial_list()
         .update_with_wrapping_monic_monomial_div(rotation);
 
+    let rotations: Vec<MonomialDegree> = lwe_mask.mask_element_iter()
+        .map(|element| pbs_modulus_switch(*element, poly_size, ModulusSwitchOffset(0), LutCountLog(0)))
+        .collect();
+    blind_rotate(accumulator, &rotations, bootstrappingkeys, buffers)?;
 
-    for (index, element) in lwe_mask.mask_element_iter().enumerate() {
+    Ok(accumulator.clone())
+}
 
-        let mut addition = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-        let pbs_switch = pbs_modulus_switch(*element,ctx.poly_size,ModulusSwitchOffset(0),LutCountLog(0)).0;
-        let bsk = bootstrappingkeys.get(index).unwrap();
-        
-        for party in 1..ctx.k+1 { 
+//Blind rotation: multiply acc by X^(rotations[0] s_0 + ... + rotations[n-1] s_(n-1)), where s_i is
+//the i-th coordinate of the (joint) LWE key and keys[i] is its indicator list (see
+//`homomorphic_indicator`). For every coordinate the selector sum_j (X^(rotations[i] j) - 1) RGSW(ind_j)
+//encrypts X^(rotations[i] s_i) - 1 and acc += selector ⊡ acc, one external product per coordinate
+//whatever the number of parties.
+//A single-key bootstrapping key is the case k = 1, where the lists are [RGSW(1 - s_i), RGSW(s_i)]:
+//the step is then the CMux of acc and X^rotations[i] acc by RGSW(s_i).
+pub fn blind_rotate<T: UnsignedTorus>(
+    acc: &mut RLWECiphertext<T>,
+    rotations: &[MonomialDegree],
+    keys: &[Vec<RGSWCiphertext<T>>],
+    buffers: &mut FourierBuffers<T>,
+) -> Result<(), MfheError>
+{
+    if rotations.len() > keys.len() {
+        return Err(MfheError::MissingKeyMaterial("bootstrapping key of a LWE coordinate"));
+    }
+    let poly_size = acc.polynomial_size();
+
+    for (rotation, bsk) in rotations.iter().zip(keys.iter()) {
+        if bsk.len() == 2 {
+            let mut rotated = acc.clone();
+            rotated.update_with_monomial_mul(*rotation);
+            *acc = bsk[1].cmux_with_buf(acc, &rotated, buffers);
+            continue;
+        }
 
-            let mut aux = bsk[party].clone();
-            let degree = MonomialDegree(pbs_switch*party);
-            bsk[party].product_monomial(&mut aux, degree);
-            addition.add_ciphertext(&aux);
-            addition.sub_ciphertext(&bsk[party]);
+        let mut selector = RGSWCiphertext::allocate(poly_size, bsk[0].decomposition_base_log(), bsk[0].decomposition_level_count());
+        for (party, ct) in bsk.iter().enumerate().skip(1) {
+            let mut aux = ct.clone();
+            ct.product_monomial(&mut aux, MonomialDegree(rotation.0 * party));
+            selector.add_ciphertext(&aux);
+            selector.sub_ciphertext(ct);
+        }
+
+        let mut product = RLWECiphertext::allocate(poly_size);
+        selector.external_product_with_buf(&mut product, acc, buffers);
+        acc.update_with_add(&product);
+    }
 
+    Ok(())
+}
+
+//Same as `blind_rotate` with the bootstrapping key and the monomials in the Fourier domain.
+pub fn blind_rotate_fourier<T: UnsignedTorus>(
+    acc: &mut RLWECiphertext<T>,
+    rotations: &[MonomialDegree],
+    bootstrapping_key: &FourierBootstrappingKey<T>,
+    buffers: &mut FourierBuffers<T>,
+) -> Result<(), MfheError>
+{
+    if rotations.len() > bootstrapping_key.lwe_dimension() {
+        return Err(MfheError::MissingKeyMaterial("bootstrapping key of a LWE coordinate"));
+    }
+    let poly_size = acc.polynomial_size();
+    let base_log = bootstrapping_key.decomposition_base_log();
+    let level_count = bootstrapping_key.decomposition_level_count();
+    let mut aux = FourierRGSWCiphertext::allocate(poly_size, base_log, level_count);
+
+    for (index, rotation) in rotations.iter().enumerate() {
+        let bsk = bootstrapping_key.indicators(index);
+        if bsk.len() == 2 {
+            let mut rotated = acc.clone();
+            rotated.update_with_monomial_mul(*rotation);
+            *acc = bsk[1].cmux_with_buf(acc, &rotated, buffers);
+            continue;
         }
 
-        let mut aux = RLWECiphertext::allocate(ctx.poly_size);
-        addition.external_product(&mut aux, &accumulator);
-        accumulator.update_with_add(&aux);
+        let mut selector = FourierRGSWCiphertext::allocate(poly_size, base_log, level_count);
+        for (party, ct) in bsk.iter().enumerate().skip(1) {
+            let monomial = bootstrapping_key.monomial(MonomialDegree(rotation.0 * party));
+            ct.product_monomial(&mut aux, monomial);
+            selector.add_ciphertext(&aux);
+            selector.sub_ciphertext(ct);
+        }
 
+        let mut product = RLWECiphertext::allocate(poly_size);
+        selector.external_product_with_buf(&mut product, acc, buffers);
+        acc.update_with_add(&product);
     }
 
-    RLWECiphertext(accumulator.get_glwe_copy(&mut ctx))
+    Ok(())
 }
 
 
 
 //Function to bootstrap a LWE ciphertext in the Fourier domain.
-pub fn bootstrap_fourier (
-    lwe_in: &LWECiphertext,
-    accumulator: &mut RLWECiphertext,
-    bootstrappingkeys: Vec<Vec<FourierRGSWCiphertext>>,
-    monomials_fourier: &Vec<FourierPolynomial<AlignedVec<Complex64>>>,
-    mut ctx: &mut Context,
-) -> RLWECiphertext
+//The bootstrapping key and the monomials X^d are already in the Fourier domain, so the only
+//transforms left are the ones of the external products.
+//With lut_count_log = l the rotations are multiples of 2^l, so the 2^l lookup tables packed in the
+//accumulator can be sample extracted at degrees 0, ..., 2^l - 1.
+pub fn bootstrap_fourier<T: UnsignedTorus> (
+    lwe_in: &LWECiphertext<T>,
+    accumulator: &mut RLWECiphertext<T>,
+    bootstrapping_key: &FourierBootstrappingKey<T>,
+    lut_count_log: LutCountLog,
+    buffers: &mut FourierBuffers<T>,
+) -> Result<RLWECiphertext<T>, MfheError>
 {
+    let poly_size = accumulator.polynomial_size();
+    if lwe_in.lwe_size().0 != bootstrapping_key.lwe_dimension() + 1 {
+        return Err(MfheError::DimensionMismatch {
+            what: "bootstrapped LWE ciphertext",
+            expected: bootstrapping_key.lwe_dimension() + 1,
+            found: lwe_in.lwe_size().0,
+        });
+    }
+    if poly_size != bootstrapping_key.polynomial_size() {
+        return Err(MfheError::DimensionMismatch {
+            what: "accumulator",
+            expected: bootstrapping_key.polynomial_size().0,
+            found: poly_size.0,
+        });
+    }
+
     let (lwe_body, lwe_mask) = lwe_in.0.get_body_and_mask();
     let rotation = pbs_modulus_switch(
         lwe_body.0,
-        ctx.poly_size,
+        poly_size,
         ModulusSwitchOffset(0),
-        LutCountLog(0));
+        lut_count_log);
 
     accumulator.0.as_mut_polynomial_list()
         .update_with_wrapping_monic_monomial_div(rotation);
 
-    
-    for (index, element) in lwe_mask.mask_element_iter().enumerate() {
+    let rotations: Vec<MonomialDegree> = lwe_mask.mask_element_iter()
+        .map(|element| pbs_modulus_switch(*element, poly_size, ModulusSwitchOffset(0), lut_count_log))
+        .collect();
+    blind_rotate_fourier(accumulator, &rotations, bootstrapping_key, buffers)?;
 
-        let mut addition = FourierRGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-        let pbs_switch = pbs_modulus_switch(*element,ctx.poly_size,ModulusSwitchOffset(0),LutCountLog(0)).0;
-        let bsk = bootstrappingkeys.get(index).unwrap();
-        
-        for party in 1..ctx.k+1 { 
-
-            let mut aux = bsk[party].clone();
-            //let now = Instant::now();
-            let degree = MonomialDegree(pbs_switch*party);
-            let monomial = monomials_fourier.get(degree.0%2*ctx.poly_size.0).unwrap();
-            //let now = Instant::now();
-            bsk[party].product_monomial(&mut aux, monomial);
-            //println!("Time of monomials fourier: {} micro seconds", now.elapsed().as_micros());
-            //let now = Instant::now();
-            addition.add_ciphertext(&aux);
-            //println!("Time of add fourier: {} micro seconds", now.elapsed().as_micros());
-            //let now = Instant::now();
-            addition.sub_ciphertext(&bsk[party]);
-            //println!("Time of sub fourier: {} micro seconds", now.elapsed().as_micros());
-            
-
-        }
-
-        let mut aux = RLWECiphertext::allocate(ctx.poly_size);
-        //let now = Instant::now();
-        addition.external_product(&mut aux, &accumulator);
-        //println!("Time of external product fourier: {} micro seconds", now.elapsed().as_micros());
-        //let now = Instant::now();
-        accumulator.update_with_add(&aux);
-        //println!("Time of update accumulator: {} micro seconds", now.elapsed().as_micros());
-
-    }
-
-    RLWECiphertext(accumulator.get_glwe_copy(&mut ctx))
+    Ok(accumulator.clone())
 }
 
 //Function to bootstrap a LWE ciphertexts in the Fourier domain. It can use secret key material for debugging purpuses.
@@ -829,42 +1241,39 @@ pub fn bootstrap_fourier_debug (
 
 ///Outputs a vector [RGSW(0), ... , RGSW(1), ..., RGSW(0)] depending on the values of c. That is, the ciphertext RGSW(1)
 /// will be placed in the i-th position where i corresponds to the number of encryptions of 1 in c.
-pub fn homomorphic_indicator(global_rlwe_sk: &RLWESecretKey, c: Vec<RGSWCiphertext>, mut ctx: &mut Context) -> Vec<RGSWCiphertext> {
-    
-    let mut l: Vec<RGSWCiphertext> = Vec::new();
+//Given c = [RGSW(s_1), ..., RGSW(s_k)], the encryptions of the bits of the k parties for one LWE
+//coordinate, output [RGSW(ind_0), ..., RGSW(ind_k)] where ind_j = 1 if s_1 + ... + s_k = j and 0
+//otherwise. The initial list is the trivial encryption of [1, 0, ..., 0], so only public material is used.
+pub fn homomorphic_indicator<T: UnsignedTorus>(c: Vec<RGSWCiphertext<T>>, ctx: &mut Context) -> Vec<RGSWCiphertext<T>> {
 
-    let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-    let ptxt = RLWESecretKey::plaintext_index(0, 1, &mut ctx);
-    global_rlwe_sk.encrypt_wrapping_rgsw(&mut ct, &ptxt, &mut ctx);
-    l.push(ct);
+    let k = c.len();
+    let mut l: Vec<RGSWCiphertext<T>> = Vec::new();
 
-    for _i in 0..ctx.k {
-        let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-        let ptxt = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
-        global_rlwe_sk.encrypt_wrapping_rgsw(&mut ct, &ptxt, &mut ctx);
-        l.push(ct);
+    let mut trivial_one = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
+    trivial_one.fill_with_trivial_encryption(&RLWESecretKey::plaintext_index(0, T::ONE, ctx));
+    l.push(trivial_one.clone());
+
+    for _i in 0..k {
+        l.push(RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count));
     }
 
-    for j in 0..ctx.k {
+    for j in 0..k {
         let mut l_prime = l.clone();
 
-        let mut ct_one = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-        let ptxt = RLWESecretKey::plaintext_index(0, 1, &mut ctx);
-        global_rlwe_sk.encrypt_wrapping_rgsw(&mut ct_one, &ptxt, &mut ctx);
-        //global_rlwe_sk.trivial_encrypt_wrapping_rgsw(&mut ct_one, &ptxt, &mut ctx);
+        let mut ct_one = trivial_one.clone();
         ct_one.sub_ciphertext(&c[j]);
 
         let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-        ct_one.internal_product(&mut ct, &l[0], &mut ctx);
+        ct_one.internal_product(&mut ct, &l[0], ctx);
 
         l_prime[0] = ct;
         
-        for i in 1..ctx.k+1 {
+        for i in 1..k+1 {
             let mut aux = l[i-1].clone();
             aux.sub_ciphertext(&l[i]); 
 
             let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-            c[j].internal_product(&mut ct, &aux, &mut ctx);
+            c[j].internal_product(&mut ct, &aux, ctx);
 
             ct.add_ciphertext(&l[i]);
 
@@ -878,4 +1287,175 @@ pub fn homomorphic_indicator(global_rlwe_sk: &RLWESecretKey, c: Vec<RGSWCipherte
 
     l
 
-}
\ No newline at end of file
+}
+
+//Aggregate the contributions of the parties (the output of `LWESecretKey::encrypt_rgsw_bits`) into
+//the bootstrapping key: for every LWE coordinate, the indicator list of the sum of the bits.
+//There must be one contribution per party, each holding one RGSW ciphertext per LWE coordinate.
+pub fn generate_bootstrapping_key<T: UnsignedTorus>(party_bits: &[Vec<RGSWCiphertext<T>>], ctx: &mut Context) -> Result<Vec<Vec<RGSWCiphertext<T>>>, MfheError> {
+    if party_bits.len() != ctx.k {
+        return Err(MfheError::DimensionMismatch { what: "list of bootstrapping key shares", expected: ctx.k, found: party_bits.len() });
+    }
+    let n = ctx.glwe_size.0 - 1;
+    if let Some(bits) = party_bits.iter().find(|bits| bits.len() != n) {
+        return Err(MfheError::DimensionMismatch { what: "bootstrapping key share", expected: n, found: bits.len() });
+    }
+    Ok((0..n).map(|pos| {
+        let c: Vec<RGSWCiphertext<T>> = party_bits.iter().map(|bits| bits[pos].clone()).collect();
+        homomorphic_indicator(c, ctx)
+    }).collect())
+}
+
+//Same as `homomorphic_indicator`, computed with rayon. The k+1 internal products of a round only
+//depend on the list of the previous round, so they run in parallel, each thread with its own FFT
+//buffers. No randomness is involved and the context is not needed, so the ciphertexts of c must
+//all have the parameters of the first one.
+pub fn par_homomorphic_indicator<T: UnsignedTorus + Send + Sync>(c: &[RGSWCiphertext<T>]) -> Result<Vec<RGSWCiphertext<T>>, MfheError> {
+
+    let k = c.len();
+    let first = c.first().ok_or(ContextError::NoParties)?;
+    let poly_size = first.polynomial_size();
+    let base_log = first.decomposition_base_log();
+    let level_count = first.decomposition_level_count();
+    if let Some(ct) = c.iter().find(|ct| ct.polynomial_size() != poly_size) {
+        return Err(MfheError::DimensionMismatch { what: "RGSW polynomial", expected: poly_size.0, found: ct.polynomial_size().0 });
+    }
+    if let Some(ct) = c.iter().find(|ct| ct.decomposition_level_count() != level_count) {
+        return Err(MfheError::DimensionMismatch { what: "RGSW decomposition level count", expected: level_count.0, found: ct.decomposition_level_count().0 });
+    }
+    if let Some(ct) = c.iter().find(|ct| ct.decomposition_base_log() != base_log) {
+        return Err(MfheError::DimensionMismatch { what: "RGSW decomposition base log", expected: base_log.0, found: ct.decomposition_base_log().0 });
+    }
+    let new_buffers = || FourierBuffers::new(poly_size, GlweSize(2));
+
+    let mut trivial_one = RGSWCiphertext::allocate(poly_size, base_log, level_count);
+    trivial_one.fill_with_trivial_encryption(&RLWESecretKey::plaintext_index2(0, T::ONE, PlaintextCount(poly_size.0)));
+
+    let mut l = vec![RGSWCiphertext::allocate(poly_size, base_log, level_count); k + 1];
+    l[0] = trivial_one.clone();
+
+    for c_j in c {
+        let mut ct_one = trivial_one.clone();
+        ct_one.sub_ciphertext(c_j);
+
+        l = (0..k+1).into_par_iter().map_init(new_buffers, |buffers, i| {
+            let mut ct = RGSWCiphertext::allocate(poly_size, base_log, level_count);
+            if i == 0 {
+                ct_one.internal_product_with_buf(&mut ct, &l[0], buffers);
+            } else {
+                let mut aux = l[i-1].clone();
+                aux.sub_ciphertext(&l[i]);
+                c_j.internal_product_with_buf(&mut ct, &aux, buffers);
+                ct.add_ciphertext(&l[i]);
+            }
+            ct
+        }).collect();
+    }
+
+    Ok(l)
+}
+
+//Same as `generate_bootstrapping_key`, the LWE coordinates are spread over the rayon thread pool
+//(see `par_homomorphic_indicator`). Without a context the number of parties and n are taken from
+//party_bits, every contribution must hold as many ciphertexts as the first one.
+pub fn par_generate_bootstrapping_key<T: UnsignedTorus + Send + Sync>(party_bits: &[Vec<RGSWCiphertext<T>>]) -> Result<Vec<Vec<RGSWCiphertext<T>>>, MfheError> {
+    let n = party_bits.first().ok_or(ContextError::NoParties)?.len();
+    if n == 0 {
+        return Err(ContextError::EmptyLweDimension.into());
+    }
+    if let Some(bits) = party_bits.iter().find(|bits| bits.len() != n) {
+        return Err(MfheError::DimensionMismatch { what: "bootstrapping key share", expected: n, found: bits.len() });
+    }
+    (0..n).into_par_iter().map(|pos| {
+        let c: Vec<RGSWCiphertext<T>> = party_bits.iter().map(|bits| bits[pos].clone()).collect();
+        par_homomorphic_indicator(&c)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ccore::crypto::encoding::PlaintextList;
+    use concrete_commons::parameters::PlaintextCount;
+    use crate::params::{ContextBuilder, SecurityLevel};
+
+    /// The messages of the test polynomial are in [0, 8), on the 3 most significant bits.
+    const SHIFT: usize = Scalar::BITS as usize - 3;
+    const ROTATION: usize = 5;
+
+    fn test_polynomial(poly_size: usize) -> Vec<Scalar> {
+        (0..poly_size).map(|i| (i % 8) as Scalar).collect()
+    }
+
+    /// The test polynomial times X^(ROTATION * bit), modulo X^N + 1 and 8.
+    fn rotated(bit: Scalar, poly_size: usize) -> Vec<Scalar> {
+        let messages = test_polynomial(poly_size);
+        let rotation = ROTATION * bit as usize;
+        (0..poly_size).map(|j| if j >= rotation {
+            messages[j - rotation]
+        } else {
+            (8 - messages[poly_size + j - rotation]) % 8
+        }).collect()
+    }
+
+    /// Blind rotate an encryption of the test polynomial with the indicator list of bit, once with
+    /// the list of k = 1 party (the CMux path) and once with the list of k = 2 parties, the second
+    /// one holding a 0 (the general path). Return both decrypted messages.
+    fn rotate_both_ways(bit: Scalar, fourier: bool) -> (Vec<Scalar>, Vec<Scalar>) {
+        let mut ctx = ContextBuilder::preset(1, SecurityLevel::Insecure).and_then(|builder| builder.build()).unwrap();
+        let sk = RLWESecretKey::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
+        let encrypt = |value: Scalar, ctx: &mut Context| {
+            let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
+            sk.encrypt_constant_rgsw(&mut ct, &Plaintext(value), ctx);
+            ct
+        };
+        let s = encrypt(bit, &mut ctx);
+        let zero = encrypt(0, &mut ctx);
+        let cmux_list = par_homomorphic_indicator(std::slice::from_ref(&s)).unwrap();
+        let general_list = par_homomorphic_indicator(&[s, zero]).unwrap();
+        assert_eq!((cmux_list.len(), general_list.len()), (2, 3));
+
+        let mut acc = RLWECiphertext::allocate(ctx.poly_size);
+        let messages = test_polynomial(ctx.poly_size.0);
+        for (coeff, message) in acc.get_mut_body().as_mut_tensor().iter_mut().zip(messages.iter()) {
+            *coeff = message << SHIFT;
+        }
+
+        let mut buffers = ctx.gen_fourier_buffers();
+        let rotations = [MonomialDegree(ROTATION)];
+        let mut rotate = |list: Vec<RGSWCiphertext>| {
+            let keys = vec![list];
+            let mut out = acc.clone();
+            if fourier {
+                let bsk = FourierBootstrappingKey::from_standard(&keys, &mut buffers).unwrap();
+                blind_rotate_fourier(&mut out, &rotations, &bsk, &mut buffers).unwrap();
+            } else {
+                blind_rotate(&mut out, &rotations, &keys, &mut buffers).unwrap();
+            }
+            let mut pt = PlaintextList::allocate(0, PlaintextCount(ctx.poly_size.0));
+            sk.decrypt_rlwe(&mut pt, &out);
+            pt.as_tensor().iter().map(|x| x.wrapping_add(1 << (SHIFT - 1)) >> SHIFT).collect::<Vec<Scalar>>()
+        };
+        let cmux = rotate(cmux_list);
+        let general = rotate(general_list);
+        (cmux, general)
+    }
+
+    #[test]
+    fn blind_rotate_cmux_matches_indicator_list() {
+        for bit in [0, 1] {
+            let (cmux, general) = rotate_both_ways(bit, false);
+            assert_eq!(cmux, general);
+            assert_eq!(cmux, rotated(bit, cmux.len()));
+        }
+    }
+
+    #[test]
+    fn blind_rotate_fourier_cmux_matches_indicator_list() {
+        for bit in [0, 1] {
+            let (cmux, general) = rotate_both_ways(bit, true);
+            assert_eq!(cmux, general);
+            assert_eq!(cmux, rotated(bit, cmux.len()));
+        }
+    }
+}
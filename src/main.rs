@@ -1,109 +1,101 @@
 use bitvec::macros::internal::funty::Integral;
-use concrete_commons::numeric::CastInto;
-use concrete_commons::parameters::LweDimension;
-use concrete_core::backends::core::private::crypto::encoding::PlaintextList;
-use concrete_core::backends::core::private::crypto::lwe::LweBody;
+use concrete_commons::dispersion::{LogStandardDev, StandardDev};
+use concrete_commons::parameters::{DecompositionBaseLog, DecompositionLevelCount};
 
 use concrete_core::backends::core::private::math::tensor::AsMutTensor;
-use concrete_core::backends::core::private::math::tensor::AsRefTensor;
 
 use mfhebrk::decode_gate;
-use mfhebrk::decode_gate32;
 use mfhebrk::encode_accumulator;
-use mfhebrk::encode_accumulator32;
 use mfhebrk::encode_gate;
-use mfhebrk::encode_gate32;
 use mfhebrk::lwe::LWECiphertext;
-use mfhebrk::lwe::LWECiphertext32;
-use mfhebrk::lwe::LWEKeyswitchKey;
-use mfhebrk::lwe::LWEPublicKey;
-use mfhebrk::lwe::LWESecretKey;
-use mfhebrk::lwe::compute_noise_lwe;
-use mfhebrk::lwe::homomorphic_indicator;
-use mfhebrk::plaintext_index;
-use mfhebrk::poly_encode;
-
-use mfhebrk::rlwe::RLWEPublicKey;
-use mfhebrk::lwe::constant_sample_extract;
+use mfhebrk::lwe::DecryptionShare;
+
 use mfhebrk::Scalar;
 
 use concrete_core::backends::core::private as ccore;
-use mfhebrk::Context;
+use mfhebrk::params::{ContextBuilder, SecurityLevel};
+use mfhebrk::profiling::NoiseProfiler;
+use mfhebrk::circuit_bootstrap::circuit_bootstrap;
+use mfhebrk::lwe::constant_sample_extract;
+use mfhebrk::party::{BootstrappingKeyShare, CircuitBootstrappingKeyShare, CompressedPublicKeyShare, Coordinator, Party, PublicKeyShare};
 use mfhebrk::rgsw;
 use mfhebrk::rlwe;
-use crate::rgsw::RGSWCiphertext;
-use crate::rlwe::RLWECiphertext;
 use num_traits::identities::One;
-use num_traits::identities::Zero;
 use crate::ccore::crypto::encoding::Plaintext;
-use mfhebrk::rlwe::RLWESecretKey;
 
 use concrete_core::prelude::*;
+use get_size::GetSize;
 
-use mfhebrk::lwe::bootstrap;
+use mfhebrk::error::MfheError;
+use mfhebrk::gates::nand;
+use mfhebrk::lut::{bootstrap_batch, decode_message, encode_message, multi_value_programmable_bootstrap, programmable_bootstrap, LookupTable, MultiValueLookupTable};
+
+use std::fs::File;
 
 extern crate concrete_boolean;
 
 extern crate rand;
 
 /// We provide an example of the blind rotation set up and the computation of a NAND gate using (multiparty) TFHE bootstrapping
-fn main() {
+fn main() -> Result<(), MfheError> {
 
     //Initialize the context
-    let mut ctx = Context::default();
-
-    //PUBLIC, SECRET AND KEY SWITCHING KEYS SETUP
-
-    let mask_lwe_sk = LWESecretKey::generate_binary(LweDimension(ctx.glwe_size.0), &mut ctx.secret_generator);
-    let mask_rlwe_sk = RLWESecretKey::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
-    
-    let mut global_rlwe_pk = RLWEPublicKey::allocate(ctx.poly_size, GlweSize(2), ctx.m);
-    let mut global_rlwe_sk = RLWESecretKey::zero(ctx.poly_size);
+    let mut ctx = ContextBuilder::preset(4, SecurityLevel::Default)
+        .and_then(|builder| builder.build())?;
+
+    //TESTING REPLAY (two contexts built from the same master seed in replay mode give the same
+    //ciphertext). The seeds determine every secret, so they are only printed in replay mode.
+    #[cfg(feature = "replay")]
+    {
+        use concrete_core::backends::core::private::math::tensor::AsRefTensor;
+        use mfhebrk::lwe::LWESecretKey;
+
+        println!("Seeds: {:?}", ctx.seeds);
+
+        let master_seed = 0x5eed;
+        let mut replayed = Vec::new();
+        for _ in 0..2 {
+            let mut replay_ctx = ContextBuilder::from_context(&ctx).seed(master_seed).replay(true).build()?;
+            let sk = LWESecretKey::generate_binary(LweDimension(replay_ctx.glwe_size.0 - 1), &mut replay_ctx.secret_generator);
+            let mut ct = LWECiphertext::allocate(LweSize(replay_ctx.glwe_size.0));
+            sk.binary_encrypt_lwe(&mut ct, &Plaintext(1), &mut replay_ctx)?;
+            replayed.push(ct);
+        }
+        let identical = replayed[0].get_body().0 == replayed[1].get_body().0
+            && replayed[0].get_mask().as_tensor() == replayed[1].get_mask().as_tensor();
+        println!("Replay with the same master seed: {}", if identical { "identical" } else { "different" });
+    }
 
-    let mut global_lwe_pk = LWEPublicKey::allocate(ctx.glwe_size.0, ctx.m);
-    let mut global_lwe_sk = LWESecretKey::zero(ctx.glwe_size.0 - 1);
+    println!("Predicted key sizes for k = {}:\n{}", ctx.k, ctx.key_size_report());
+    println!("Predicted noise for k = {}:\n{}", ctx.k, ctx.noise_report());
 
-    //We generate the global masks for the global public keys
-    mask_rlwe_sk.generate_mask(&mut global_rlwe_pk, &mut ctx);
-    mask_lwe_sk.generate_mask(&mut global_lwe_pk, &mut ctx);
+    //PUBLIC, SECRET AND KEY SWITCHING KEYS SETUP
 
-    let mut lwe_sk_list: Vec<LWESecretKey> = Vec::new();
-    let mut rlwe_sk_list: Vec<RLWESecretKey> = Vec::new();
-    let mut rlwe_pk_list: Vec<RLWEPublicKey> = Vec::new();
-    
-    print!("Generating global pk,sk and ksk keys...");
+    //The coordinator publishes the seed of the common masks of the public keys and of the
+    //keyswitching key, every party expands it locally
+    let mut coordinator: Coordinator = Coordinator::new(&ctx)?;
+    let crs = coordinator.common_reference_string();
 
-    for _user in 0..ctx.k {
-        let lwe_sk = LWESecretKey::generate_binary(LweDimension(ctx.glwe_size.0 - 1), &mut ctx.secret_generator);
-        let rlwe_sk = RLWESecretKey::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
-        let mut rlwe_pk = RLWEPublicKey::allocate(ctx.poly_size, GlweSize(2), ctx.m);
-        let mut lwe_pk = LWEPublicKey::allocate(ctx.glwe_size.0, ctx.m);
+    println!("CRS seed: {:#x}", crs.seed());
 
-        rlwe_pk.update_with_global_mask(&global_rlwe_pk, &ctx);
-        lwe_pk.update_with_global_mask(&global_lwe_pk, &ctx);
-        
-        global_rlwe_pk.compare_mask(&rlwe_pk, ctx.m);
-        global_lwe_pk.compare_mask(&lwe_pk, ctx.m);
+    let mut parties: Vec<Party> = (0..ctx.k).map(|id| Party::new(id, &ctx)).collect::<Result<_, _>>()?;
 
-        rlwe_sk.fill_rlwe_pk(&mut rlwe_pk,  ctx.std_rlwe, &mut ctx.encryption_generator, ctx.m); //fill the body of the individual rlwe public key
-        lwe_sk.fill_lwe_pk(&mut lwe_pk, ctx.std_lwe, &mut ctx.encryption_generator, ctx.m); //fill the body of the individual lwe public key
+    print!("Generating global pk and ksk keys...");
 
-        global_rlwe_pk.update_body_with_pk(&rlwe_pk, &ctx);
-        global_rlwe_sk.as_mut_tensor().update_with_wrapping_add(rlwe_sk.as_tensor());
+    //The shares are sent without their masks, the coordinator regenerates them from the CRS
+    let compressed_shares: Vec<CompressedPublicKeyShare> = parties.iter_mut()
+        .map(|party| party.public_key_share(&crs).compress(&crs))
+        .collect();
 
-        global_lwe_pk.update_body_with_pk(&lwe_pk, &ctx);
-        global_lwe_sk.as_mut_tensor().update_with_wrapping_add(lwe_sk.as_tensor());
+    let pk_shares: Vec<PublicKeyShare> = compressed_shares.iter()
+        .map(|share| share.decompress(&ctx))
+        .collect::<Result<_, _>>()?;
 
-        lwe_sk_list.push(lwe_sk);
-        rlwe_sk_list.push(rlwe_sk);
-        rlwe_pk_list.push(rlwe_pk);
+    let joint_public_key = coordinator.aggregate_public_keys(&pk_shares)?.clone();
 
+    for party in parties.iter_mut() {
+        party.receive_joint_public_key(&joint_public_key)?;
     }
-    
-    let mut ksk_lwe = LWEKeyswitchKey::allocate(LweDimension(ctx.poly_size.0),LweDimension(ctx.glwe_size.0 - 1), &ctx);
-    let sk_extraction = LWESecretKey::from_concrete_sk(global_rlwe_sk.to_lwe_secretkey(&mut ctx));
-
-    ksk_lwe.fill_with_keyswitching_key(&sk_extraction, &global_lwe_sk, &mut ctx);
 
     println!("Done");
 
@@ -111,175 +103,239 @@ fn main() {
 
     print!("Generating global bootstrapping keys...");
 
-    let mut bootstrappingkeys: Vec<Vec<RGSWCiphertext>> = Vec::new();
-
-    for pos in 0..ctx.glwe_size.0 - 1 {
-    
-        let mut c: Vec<RGSWCiphertext> = Vec::new();
-        let mut p: Vec<PlaintextList<Vec<Scalar>>> = Vec::new();
-        
-        for user in 0..ctx.k {
-
-            let mut ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
-            
-            let sk_user = lwe_sk_list[user].clone();
-            let sk_vec = sk_user.as_tensor().as_container().to_vec();
-        
-            if sk_vec.get(pos) == Some(&Scalar::zero()) {
-                let mut ptxt = plaintext_index(0, 0, &mut ctx);
-                p.push(ptxt.clone());
-                global_rlwe_sk.encrypt_wrapping_rgsw(&mut ct, &ptxt, &mut ctx);
-                poly_encode(&mut ptxt.as_mut_polynomial(),&ctx);
-                //let noise_bski = compute_noise_rgsw(&ct, &ptxt, &global_rlwe_sk, &ctx);
-            } else {
-                let mut ptxt = plaintext_index(0, 1, &mut ctx);
-                p.push(ptxt.clone());
-                global_rlwe_sk.encrypt_wrapping_rgsw(&mut ct, &ptxt, &mut ctx);
-                poly_encode(&mut ptxt.as_mut_polynomial(),&ctx);
-                //let noise_bski = compute_noise_rgsw(&ct, &ptxt, &global_rlwe_sk, &ctx);
-            }
-            
-            c.push(ct);
-        }
+    //Every party encrypts the bits of its LWE key under the joint RLWE public key and the
+    //coordinator computes the indicator lists from these public contributions only
+    let bsk_shares: Vec<BootstrappingKeyShare> = parties.iter_mut()
+        .map(|party| party.bootstrapping_key_share())
+        .collect::<Result<_, _>>()?;
 
-        let l = homomorphic_indicator(&global_rlwe_sk, c, &mut ctx);
+    let server_key = coordinator.aggregate_bootstrapping_key(&bsk_shares)?;
 
-        bootstrappingkeys.push(l);
-
-    }
+    println!("Server key size: {:.2} MiB", GetSize::get_heap_size(&server_key) as f64 / (1024. * 1024.));
 
     println!("Done");
 
     //TESTING GATE BOOTSTRAPPING (NAND gate)
     println!("Computing NAND gate...");
 
-    let mut lwe_ct = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
-
     let mut lwe_ct1 = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
     let mut lwe_ct2 = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
 
-    let mut lwe_fresh = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
-    let mut lwe_out = LWECiphertext::allocate(LweSize(ctx.poly_size.0+1));
-
-
     let cleartext1 = Scalar::one();
     let cleartext2 = Scalar::one();
 
     let cleartext1_u32 = 1u32;
     let cleartext2_u32 = 1u32;
 
-    //AND GATE
-    //let mut result = cleartext1 * cleartext2;
-    //encode_gate(&mut result, &ctx);
-
-
-    //NAND GATE
     let mut result = Scalar::one() - cleartext1 * cleartext2;
     encode_gate(&mut result);
 
     let mut result32 = 1u32 - cleartext1_u32 * cleartext2_u32;
-    encode_gate32(&mut result32);
+    encode_gate(&mut result32);
 
     let mut pt1 = cleartext1.clone();
     let mut pt2 = cleartext2.clone();
-    
-    //AND GATE CONSTANT
-    //let mut pt_constant = Scalar::zero();
-
-    //NAND GATE CONSTANT
-    let mut pt_constant = Scalar::one();
-    encode_gate(&mut pt_constant);
-    //pt_constant = pt_constant.wrapping_neg();
 
     encode_gate(&mut pt1);
     encode_gate(&mut pt2);
-    //encode_gate(&mut pt_constant, &ctx);
 
-    global_lwe_pk.encrypt_lwe(&mut lwe_ct1, &Plaintext(pt1), &mut ctx.secret_generator, ctx.m);
-    global_lwe_pk.encrypt_lwe(&mut lwe_ct2, &Plaintext(pt2), &mut ctx.secret_generator, ctx.m);
-    global_lwe_pk.encrypt_lwe(&mut lwe_fresh, &Plaintext(pt1), &mut ctx.secret_generator, ctx.m);
-    
-    //AND GATE
-    //lwe_ct.update_with_add(lwe_ct1);
-    //lwe_ct.update_with_add(lwe_ct2);
+    joint_public_key.lwe_pk.encrypt_lwe(&mut lwe_ct1, &Plaintext(pt1), &mut ctx.secret_generator, ctx.m)?;
+    joint_public_key.lwe_pk.encrypt_lwe(&mut lwe_ct2, &Plaintext(pt2), &mut ctx.secret_generator, ctx.m)?;
 
-    //let updated_body = LweBody(lwe_ct.get_body().0.wrapping_add(pt_constant));
-    //lwe_ct.get_mut_body().clone_from(&updated_body);
+    let switched_ciphertext = nand(&lwe_ct1, &lwe_ct2, &server_key)?;
 
-    //NAND GATE
+    //Threshold decryption: every party publishes a share, the global key is never used
+    let smudging_noise = LogStandardDev::from_log_standard_dev(-40.);
+    let shares: Vec<DecryptionShare> = parties.iter_mut()
+        .map(|party| party.decryption_share(&switched_ciphertext, smudging_noise))
+        .collect();
+    let mut out_keyswitch = coordinator.combine_decryption_shares(&switched_ciphertext, &shares)?;
 
-    lwe_ct.update_with_sub(lwe_ct1);
-    lwe_ct.update_with_sub(lwe_ct2);
-    
-    let updated_body = LweBody(lwe_ct.get_body().0.wrapping_add(pt_constant));
-    lwe_ct.get_mut_body().clone_from(&updated_body);
-
-    let mut accumulator =
-            RLWECiphertext::allocate(ctx.poly_size);
-    accumulator.get_mut_body().as_mut_tensor().iter_mut().enumerate().for_each(|(_i, a)| {
-        //*a = (i as Scalar) *  Scalar::one() << ( ((Scalar::BITS as usize) - (ctx.poly_size.0 as f64).log2() as usize - 1));
-        *a = Scalar::one() << ((Scalar::BITS as usize) - 3);
-    });
-
-    let output_bootstrap = bootstrap(&lwe_ct, &mut accumulator, bootstrappingkeys, &mut ctx);
-
-    let mut pt_bootstrap = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
-    global_rlwe_sk.decrypt_wrapping_rlwe(&mut pt_bootstrap, &output_bootstrap);
-
-    constant_sample_extract(&mut lwe_out, &output_bootstrap);
+    encode_accumulator(&mut out_keyswitch.0);
+    let correct = out_keyswitch.0 == result;
+    decode_gate(&mut out_keyswitch.0);
 
-    let sk_extraction = LWESecretKey::from_concrete_sk(global_rlwe_sk.to_lwe_secretkey(&mut ctx));
+    println!("NAND({:?}, {:?}) = {:?} ({})", cleartext1, cleartext2, out_keyswitch.0, if correct { "correct" } else { "wrong" });
 
-    let mut out_plaintext = Plaintext(Scalar::ZERO);
+    //TESTING PROGRAMMABLE BOOTSTRAPPING (x -> x^2 mod 4)
+    println!("Computing programmable bootstrapping...");
 
-    sk_extraction.decrypt_wrapping_lwe(&mut out_plaintext, &lwe_out);
+    let message_modulus = 4;
+    let lut = LookupTable::new(|x| x * x, message_modulus, ctx.poly_size)?;
 
-    encode_accumulator(&mut out_plaintext.0);
-    decode_gate(&mut out_plaintext.0);
+    let message = 3;
+    let mut pt = message;
+    encode_message(&mut pt, message_modulus)?;
 
-    let mut switched_ciphertext = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
-    ksk_lwe.keyswitch_ciphertext(&mut switched_ciphertext, &lwe_out);
+    let mut lwe_ct = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
+    joint_public_key.lwe_pk.encrypt_lwe(&mut lwe_ct, &Plaintext(pt), &mut ctx.secret_generator, ctx.m)?;
 
-    let mut out_keyswitch = Plaintext(Scalar::ZERO);
-    global_lwe_sk.decrypt_wrapping_lwe(&mut out_keyswitch, &switched_ciphertext);
+    let pbs_ciphertext = programmable_bootstrap(&lwe_ct, &lut, &server_key)?;
 
-    encode_accumulator(&mut out_keyswitch.0);
-    decode_gate(&mut out_keyswitch.0);
+    let shares: Vec<DecryptionShare> = parties.iter_mut()
+        .map(|party| party.decryption_share(&pbs_ciphertext, smudging_noise))
+        .collect();
+    let mut out_pbs = coordinator.combine_decryption_shares(&pbs_ciphertext, &shares)?;
+    decode_message(&mut out_pbs.0, message_modulus);
 
-    let output_noise = compute_noise_lwe(&global_lwe_sk, &switched_ciphertext, result);
-    println!("Output noise: {:?}", output_noise);
-    
-    let modulus_switch = |input: Scalar| -> usize {
-        let tmp: f64 = (input as f64)/4294967296.0;
-        let input_hat: usize = tmp.round().cast_into();
-        input_hat
-    };
+    println!("f({:?}) = {:?}", message, out_pbs.0);
 
-    let mut switched_ciphertext_mod = LWECiphertext32::allocate(LweSize(ctx.glwe_size.0));
+    //TESTING BATCH BOOTSTRAPPING (x -> x^2 mod 4 on every message at once)
+    println!("Computing batch programmable bootstrapping...");
 
-    for (i,mask) in switched_ciphertext.get_mask().mask_element_iter().enumerate() {
-        *switched_ciphertext_mod.get_mut_mask().as_mut_tensor().get_element_mut(i) = modulus_switch(*mask) as u32;
+    let mut batch = Vec::new();
+    for message in 0..message_modulus as Scalar {
+        let mut pt = message;
+        encode_message(&mut pt, message_modulus)?;
+        let mut ct = LWECiphertext::allocate(LweSize(ctx.glwe_size.0));
+        joint_public_key.lwe_pk.encrypt_lwe(&mut ct, &Plaintext(pt), &mut ctx.secret_generator, ctx.m)?;
+        batch.push(ct);
     }
 
-    switched_ciphertext_mod.get_mut_body().0 = modulus_switch(switched_ciphertext.get_body().0) as u32;
-
-    let mut out_keyswitch_mod = Plaintext(0u32);
+    let batch_ciphertexts = bootstrap_batch(&batch, &lut, &server_key)?;
 
-    let body = switched_ciphertext_mod.get_body();
-    let masks = switched_ciphertext_mod.get_mask();
+    for (message, ct) in batch_ciphertexts.iter().enumerate() {
+        let shares: Vec<DecryptionShare> = parties.iter_mut()
+            .map(|party| party.decryption_share(ct, smudging_noise))
+            .collect();
+        let mut out_batch = coordinator.combine_decryption_shares(ct, &shares)?;
+        decode_message(&mut out_batch.0, message_modulus);
+        println!("f({:?}) = {:?}", message, out_batch.0);
+    }
 
-    out_keyswitch_mod.0 = body.0;
+    //TESTING MULTI-VALUE BOOTSTRAPPING (x -> x^2 mod 4 and x -> x + 1 mod 4)
+    println!("Computing multi-value bootstrapping...");
 
-    let sub = masks.as_tensor().fold_with_one(
-        global_lwe_sk.as_tensor(),
-        0u32,
-        |ac, s_i, o_i| ac.wrapping_add(s_i.wrapping_mul((*o_i).try_into().unwrap())),
-    );
+    let square = |x: u64| x * x;
+    let increment = |x: u64| x + 1;
+    let mv_lut = MultiValueLookupTable::new(&[&square, &increment], message_modulus, ctx.poly_size)?;
 
-    out_keyswitch_mod.0 = out_keyswitch_mod.0.wrapping_sub(sub);
+    let mv_ciphertexts = multi_value_programmable_bootstrap(&lwe_ct, &mv_lut, &server_key)?;
 
-    encode_accumulator32(&mut out_keyswitch_mod.0);
-    decode_gate32(&mut out_keyswitch_mod.0);
+    for (j, ct) in mv_ciphertexts.iter().enumerate() {
+        let shares: Vec<DecryptionShare> = parties.iter_mut()
+            .map(|party| party.decryption_share(ct, smudging_noise))
+            .collect();
+        let mut out_mv = coordinator.combine_decryption_shares(ct, &shares)?;
+        decode_message(&mut out_mv.0, message_modulus);
+        println!("f_{:?}({:?}) = {:?}", j, message, out_mv.0);
+    }
     
+    //TESTING MODULUS SWITCHING TO 32 BITS (the output stays on the 64-bit torus, so the parties
+    //decrypt it as any other ciphertext)
+    let switched_ciphertext_mod = switched_ciphertext.modulus_switch(32)?;
+
+    let shares: Vec<DecryptionShare> = parties.iter_mut()
+        .map(|party| party.decryption_share(&switched_ciphertext_mod, smudging_noise))
+        .collect();
+    let mut out_keyswitch_mod = coordinator.combine_decryption_shares(&switched_ciphertext_mod, &shares)?;
+
+    encode_accumulator(&mut out_keyswitch_mod.0);
+    decode_gate(&mut out_keyswitch_mod.0);
+
+    println!("NAND({:?}, {:?}) mod 2^32 = {:?}", cleartext1, cleartext2, out_keyswitch_mod.0);
+
+    //TESTING CIRCUIT BOOTSTRAPPING (the LWE encryption of cleartext1 as the selector of a CMux)
+    println!("Computing circuit bootstrapping...");
+
+    let cbs_shares: Vec<CircuitBootstrappingKeyShare> = parties.iter_mut()
+        .map(|party| party.circuit_bootstrapping_key_share())
+        .collect::<Result<_, _>>()?;
+    let cbs_key = coordinator.aggregate_circuit_bootstrapping_key(&cbs_shares)?;
+
+    let selector = circuit_bootstrap(&lwe_ct1, &server_key, &cbs_key, &ctx)?;
+
+    //Trivial encryptions of the gate encodings of 0 and 1
+    let (mut pt_false, mut pt_true): (Scalar, Scalar) = (0, 1);
+    encode_gate(&mut pt_false);
+    encode_gate(&mut pt_true);
+    let mut ct_false = rlwe::RLWECiphertext::allocate(ctx.poly_size);
+    let mut ct_true = rlwe::RLWECiphertext::allocate(ctx.poly_size);
+    ct_false.get_mut_body().as_mut_tensor().fill_with_element(pt_false);
+    ct_true.get_mut_body().as_mut_tensor().fill_with_element(pt_true);
+
+    let selected = selector.cmux(&ct_false, &ct_true);
+
+    let mut extracted = LWECiphertext::allocate(LweSize(ctx.poly_size.0 + 1));
+    constant_sample_extract(&mut extracted, &selected);
+    let mut cmux_ciphertext = LWECiphertext::allocate(server_key.lwe_size());
+    server_key.keyswitch(&mut cmux_ciphertext, &extracted);
+
+    let shares: Vec<DecryptionShare> = parties.iter_mut()
+        .map(|party| party.decryption_share(&cmux_ciphertext, smudging_noise))
+        .collect();
+    let mut out_cmux = coordinator.combine_decryption_shares(&cmux_ciphertext, &shares)?;
+    encode_accumulator(&mut out_cmux.0);
+    decode_gate(&mut out_cmux.0);
+
+    println!("CMux(CBS({:?}), 0, 1) = {:?}", cleartext1, out_cmux.0);
+
+    //TESTING THE 32-BIT TORUS (the same protocol with u32 keys and ciphertexts, on insecure
+    //parameters whose noise and decompositions fit in 32 bits)
+    println!("Computing NAND gate on the 32-bit torus...");
+
+    let mut ctx32 = ContextBuilder::preset(2, SecurityLevel::Insecure)?
+        .torus_bits(32)
+        .std(LogStandardDev::from_log_standard_dev(-25.))
+        .std_lwe(LogStandardDev::from_log_standard_dev(-20.))
+        .std_rlwe(StandardDev::from_standard_dev(2f64.powi(-30)))
+        .level_count(DecompositionLevelCount(8))
+        .ks_base_log(DecompositionBaseLog(4))
+        .ks_level_count(DecompositionLevelCount(6))
+        .rgsw_base_log(DecompositionBaseLog(4))
+        .rgsw_level_count(DecompositionLevelCount(8))
+        .negs_base_log(DecompositionBaseLog(15))
+        .build()?;
+
+    let mut coordinator32: Coordinator<u32> = Coordinator::new(&ctx32)?;
+    let crs32 = coordinator32.common_reference_string();
+    let mut parties32: Vec<Party<u32>> = (0..ctx32.k).map(|id| Party::new(id, &ctx32)).collect::<Result<_, _>>()?;
+
+    let pk_shares32: Vec<PublicKeyShare<u32>> = parties32.iter_mut()
+        .map(|party| party.public_key_share(&crs32))
+        .collect();
+    let joint_public_key32 = coordinator32.aggregate_public_keys(&pk_shares32)?.clone();
+    for party in parties32.iter_mut() {
+        party.receive_joint_public_key(&joint_public_key32)?;
+    }
+
+    let bsk_shares32: Vec<BootstrappingKeyShare<u32>> = parties32.iter_mut()
+        .map(|party| party.bootstrapping_key_share())
+        .collect::<Result<_, _>>()?;
+    let server_key32 = coordinator32.aggregate_bootstrapping_key(&bsk_shares32)?;
+
+    let (mut pt1_u32, mut pt2_u32) = (cleartext1_u32, cleartext2_u32);
+    encode_gate(&mut pt1_u32);
+    encode_gate(&mut pt2_u32);
+    let mut lwe_ct1_u32 = LWECiphertext::allocate(LweSize(ctx32.glwe_size.0));
+    let mut lwe_ct2_u32 = LWECiphertext::allocate(LweSize(ctx32.glwe_size.0));
+    joint_public_key32.lwe_pk.encrypt_lwe(&mut lwe_ct1_u32, &Plaintext(pt1_u32), &mut ctx32.secret_generator, ctx32.m)?;
+    joint_public_key32.lwe_pk.encrypt_lwe(&mut lwe_ct2_u32, &Plaintext(pt2_u32), &mut ctx32.secret_generator, ctx32.m)?;
+
+    let nand_u32 = nand(&lwe_ct1_u32, &lwe_ct2_u32, &server_key32)?;
+
+    let smudging_noise32 = LogStandardDev::from_log_standard_dev(-20.);
+    let shares32: Vec<DecryptionShare<u32>> = parties32.iter_mut()
+        .map(|party| party.decryption_share(&nand_u32, smudging_noise32))
+        .collect();
+    let mut out_u32 = coordinator32.combine_decryption_shares(&nand_u32, &shares32)?;
+    encode_accumulator(&mut out_u32.0);
+    let correct = out_u32.0 == result32;
+    decode_gate(&mut out_u32.0);
+
+    println!("NAND({:?}, {:?}) = {:?} ({})", cleartext1_u32, cleartext2_u32, out_u32.0, if correct { "correct" } else { "wrong" });
+
+    //NOISE PROFILING (the noise after every stage of 8 NAND gates, written as CSV)
+    println!("Profiling the noise...");
+
+    let mut profiler = NoiseProfiler::new("default", &parties, &joint_public_key, &server_key, &ctx);
+    profiler.run(8, &mut ctx)?;
+
+    let path = format!("noise_profile_k{}.csv", ctx.k);
+    match File::create(&path).map_err(csv::Error::from).and_then(|file| profiler.write_csv(file)) {
+        Ok(()) => println!("Noise profile written to {}", path),
+        Err(err) => println!("Could not write {}: {}", path, err),
+    }
 
+    Ok(())
 }
\ No newline at end of file
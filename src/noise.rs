@@ -0,0 +1,241 @@
+use std::fmt;
+
+use concrete_commons::dispersion::{DispersionParameter, Variance};
+use concrete_commons::key_kinds::BinaryKeyKind;
+use concrete_commons::parameters::GlweDimension;
+use concrete_npe::estimate_external_product_noise_with_binary_ggsw;
+use crate::*;
+
+// Analytic estimates of the noise of the multiparty bootstrapping. All the variances are on the
+// torus, i.e. for q = 1, and the noises are assumed independent and centered. The estimates only
+// depend on the parameters, no key or trial run is needed.
+//
+// The joint keys are sums of k binary keys, so a coefficient of a joint key is a sum of k bits and
+// E[s^2] = k/4 + k^2/4 instead of 1/2 for a binary key: this term drives the growth with k of the
+// rounding noise of the external products, of keyswitching and of modulus switching.
+
+/// Second moment of a coefficient of a joint key of k parties.
+pub fn joint_key_second_moment(k: usize) -> f64 {
+    let k = k as f64;
+    (k + k * k) / 4.
+}
+
+/// Number of samples of the public key added by a public key encryption: one if m = 1, otherwise
+/// a random binary subset of the m samples, i.e. m/2 of them on average.
+fn public_key_samples(m: usize) -> f64 {
+    if m == 1 { 1. } else { m as f64 / 2. }
+}
+
+/// Variance of an LWE ciphertext encrypted with the joint LWE public key.
+pub fn fresh_lwe_variance(ctx: &Context) -> f64 {
+    public_key_samples(ctx.m) * ctx.k as f64 * ctx.std_lwe.get_variance()
+}
+
+/// Variance of a row of an RGSW ciphertext encrypted with the joint RLWE public key, e.g. the
+/// encryptions of the key bits sent by the parties (see `LWESecretKey::encrypt_rgsw_bits`).
+pub fn fresh_rgsw_variance(ctx: &Context) -> f64 {
+    public_key_samples(ctx.m) * ctx.k as f64 * ctx.std_rlwe.get_variance()
+}
+
+/// Variance added by the external product of an RLWE ciphertext with an RGSW ciphertext whose
+/// rows have variance rgsw_variance and whose message has squared norm message_norm2, under the
+/// joint RLWE key of k parties. The noise of the RLWE input multiplied by the message is not
+/// included.
+///
+/// The decomposition term is the one of `concrete-npe`. Its rounding term assumes a binary key and
+/// a binary message, so it is replaced by the one of the joint key and of the actual message.
+pub fn external_product_variance(ctx: &Context, k: usize, rgsw_variance: f64, message_norm2: f64) -> f64 {
+    let npe = estimate_external_product_noise_with_binary_ggsw::<Scalar, _, _, BinaryKeyKind>(
+        ctx.poly_size,
+        GlweDimension(1),
+        Variance(0.),
+        Variance(rgsw_variance),
+        ctx.rgsw_base_log,
+        ctx.rgsw_level_count,
+    ).get_variance();
+    let poly_size = ctx.poly_size.0 as f64;
+    let rounding = 2f64.powi(-2 * (ctx.rgsw_base_log.0 * ctx.rgsw_level_count.0) as i32) / 12.;
+    let binary_rounding = rounding * (1. + poly_size / 2.);
+    let joint_rounding = rounding * message_norm2 * (1. + poly_size * joint_key_second_moment(k));
+    npe - binary_rounding + joint_rounding
+}
+
+/// Variance of the rows of the output of `homomorphic_indicator` for k parties.
+///
+/// Every round is a CMux by the RGSW encryption of the bit of one party: an element of the new
+/// list is the previous element i or i - 1, selected by the bit, plus the noise of one external
+/// product. The variance hence grows linearly with the number of rounds, i.e. with k.
+pub fn indicator_variance(ctx: &Context, k: usize) -> f64 {
+    let fresh = fresh_rgsw_variance(ctx);
+    // the initial list is a trivial encryption, so it has no noise
+    let mut list = vec![0.; k + 1];
+    for _ in 0..k {
+        let product = external_product_variance(ctx, k, fresh, 1.);
+        let mut next = vec![0.; k + 1];
+        next[0] = list[0] + product;
+        for i in 1..k + 1 {
+            next[i] = list[i].max(list[i - 1]) + product;
+        }
+        list = next;
+    }
+    list.into_iter().fold(0., f64::max)
+}
+
+/// Variance of the rows of the RGSW ciphertext computed by `bootstrap` for one LWE coordinate, the
+/// sum over j = 1..k of (X^(a j) - 1) times the j-th indicator. For a nonzero rotation both terms of
+/// every product keep the noise of the indicator.
+pub fn bootstrap_sum_variance(ctx: &Context, k: usize) -> f64 {
+    2. * k as f64 * indicator_variance(ctx, k)
+}
+
+/// Variance of the accumulator after the blind rotation of `bootstrap`, starting from a trivial
+/// accumulator. Every LWE coordinate adds one external product with an RGSW encryption of
+/// X^(a s) - 1, whose squared norm is 2, while the rotation keeps the noise of the accumulator.
+pub fn blind_rotation_variance(ctx: &Context) -> f64 {
+    let n = (ctx.glwe_size.0 - 1) as f64;
+    n * external_product_variance(ctx, ctx.k, bootstrap_sum_variance(ctx, ctx.k), 2.)
+}
+
+/// Variance added by keyswitching a sample extracted ciphertext (under the joint RLWE key) to the
+/// joint LWE key. Every share of the keyswitching key has the noise of `Context::std_rlwe`.
+pub fn keyswitch_variance(ctx: &Context) -> f64 {
+    let input_size = ctx.poly_size.0 as f64;
+    let base = (1u128 << ctx.ks_base_log.0) as f64;
+    let level_count = ctx.ks_level_count.0;
+    let ksk_variance = ctx.k as f64 * ctx.std_rlwe.get_variance();
+    let decomposition = input_size * level_count as f64 * (base * base + 2.) / 12. * ksk_variance;
+    let rounding = input_size * joint_key_second_moment(ctx.k) / (12. * base.powi(2 * level_count as i32));
+    decomposition + rounding
+}
+
+/// Variance added by rounding an LWE ciphertext under the joint LWE key to the modulus
+/// 2^log_modulus, e.g. by `LWECiphertext::modulus_switch`. The blind rotation switches to 2N.
+pub fn modulus_switch_variance(ctx: &Context, log_modulus: usize) -> f64 {
+    let n = (ctx.glwe_size.0 - 1) as f64;
+    let step = 2f64.powi(-(log_modulus as i32));
+    step * step / 12. * (1. + n * joint_key_second_moment(ctx.k))
+}
+
+/// Statistics of the errors of the coefficients of a decrypted ciphertext, as returned by
+/// `compute_noise` and its LWE and RGSW variants. The errors are the signed differences between
+/// the phase and the expected encoding, in units of 2^-64 of the torus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseStats {
+    /// The largest absolute error.
+    pub max: f64,
+    pub mean: f64,
+    pub variance: f64,
+    /// log2 of max, i.e. the number of noisy bits.
+    pub log2_max: f64,
+    /// The number of coefficients.
+    pub count: usize,
+    /// The number of coefficients whose absolute error is at least the threshold given to the
+    /// `compute_noise` function, e.g. the decoding margin.
+    pub beyond_threshold: usize,
+}
+
+impl NoiseStats {
+    /// Compute the statistics of errors, given as wrapping differences to the expected encodings.
+    pub fn from_errors(errors: impl Iterator<Item = Scalar>, threshold: Scalar) -> NoiseStats {
+        let mut max: f64 = 0.;
+        let mut sum = 0.;
+        let mut sum_squares = 0.;
+        let mut count = 0;
+        let mut beyond_threshold = 0;
+        for error in errors {
+            let signed = error as SignedScalar;
+            let abs = signed.unsigned_abs();
+            let e = signed as f64;
+            max = max.max(abs as f64);
+            sum += e;
+            sum_squares += e * e;
+            count += 1;
+            if abs >= threshold {
+                beyond_threshold += 1;
+            }
+        }
+        let mean = if count == 0 { 0. } else { sum / count as f64 };
+        let variance = if count == 0 { 0. } else { sum_squares / count as f64 - mean * mean };
+        NoiseStats { max, mean, variance, log2_max: max.log2(), count, beyond_threshold }
+    }
+}
+
+/// Base 2 logarithm of the probability that a centered Gaussian of the given variance is at
+/// least margin in absolute value, i.e. log2(erfc(margin / (sqrt(2) sigma))).
+pub fn log2_failure_probability(variance: f64, margin: f64) -> f64 {
+    if variance <= 0. {
+        return f64::NEG_INFINITY;
+    }
+    let x = margin / (2. * variance).sqrt();
+    if x < 3. {
+        // Abramowitz and Stegun 7.1.26
+        let t = 1. / (1. + 0.3275911 * x);
+        let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+        (poly * (-x * x).exp()).log2()
+    } else {
+        // asymptotic expansion, computed in the log domain since erfc(x) underflows quickly
+        let series = 1. - 1. / (2. * x * x) + 3. / (4. * x.powi(4));
+        (-x * x) * std::f64::consts::LOG2_E - (x * std::f64::consts::PI.sqrt()).log2() + series.log2()
+    }
+}
+
+/// The predicted noise of the steps of the bootstrapping for a context, as returned by
+/// `Context::noise_report`. The variances are on the torus.
+pub struct NoiseReport {
+    pub fresh_lwe: f64,
+    pub fresh_rgsw: f64,
+    /// The output of `homomorphic_indicator`, it grows linearly with k.
+    pub indicator: f64,
+    /// The sum of k monomial-scaled indicators of one coordinate in `bootstrap`.
+    pub bootstrap_sum: f64,
+    pub blind_rotation: f64,
+    pub keyswitch: f64,
+    /// The switch to the modulus 2N before the blind rotation.
+    pub modulus_switch: f64,
+    /// The input of the bootstrapping of a gate whose inputs are gate outputs or fresh ciphertexts,
+    /// including the modulus switch. XOR doubles both the inputs and the margin, so the ratio is
+    /// the same for all gates.
+    pub gate_input: f64,
+    /// log2 of the probability that a gate outputs the wrong bit.
+    pub log2_gate_failure: f64,
+}
+
+impl NoiseReport {
+    pub fn from_context(ctx: &Context) -> NoiseReport {
+        let fresh_lwe = fresh_lwe_variance(ctx);
+        let blind_rotation = blind_rotation_variance(ctx);
+        let keyswitch = keyswitch_variance(ctx);
+        let log_2n = (2 * ctx.poly_size.0).trailing_zeros() as usize;
+        let modulus_switch = modulus_switch_variance(ctx, log_2n);
+        let gate_output = blind_rotation + keyswitch;
+        let gate_input = 2. * fresh_lwe.max(gate_output) + modulus_switch;
+
+        NoiseReport {
+            fresh_lwe,
+            fresh_rgsw: fresh_rgsw_variance(ctx),
+            indicator: indicator_variance(ctx, ctx.k),
+            bootstrap_sum: bootstrap_sum_variance(ctx, ctx.k),
+            blind_rotation,
+            keyswitch,
+            modulus_switch,
+            gate_input,
+            // the encodings are +-1/8, so the phase must not move by 1/8
+            log2_gate_failure: log2_failure_probability(gate_input, 1. / 8.),
+        }
+    }
+}
+
+impl fmt::Display for NoiseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let log_std = |variance: f64| variance.sqrt().log2();
+        writeln!(f, "Fresh LWE ciphertext:  std 2^{:.2}", log_std(self.fresh_lwe))?;
+        writeln!(f, "Fresh RGSW ciphertext: std 2^{:.2}", log_std(self.fresh_rgsw))?;
+        writeln!(f, "Indicator:             std 2^{:.2}", log_std(self.indicator))?;
+        writeln!(f, "Bootstrap sum:         std 2^{:.2}", log_std(self.bootstrap_sum))?;
+        writeln!(f, "Blind rotation:        std 2^{:.2}", log_std(self.blind_rotation))?;
+        writeln!(f, "Keyswitch:             std 2^{:.2}", log_std(self.keyswitch))?;
+        writeln!(f, "Modulus switch:        std 2^{:.2}", log_std(self.modulus_switch))?;
+        writeln!(f, "Gate input:            std 2^{:.2}", log_std(self.gate_input))?;
+        write!(f, "Gate failure probability: 2^{:.2}", self.log2_gate_failure)
+    }
+}
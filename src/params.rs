@@ -0,0 +1,540 @@
+use std::fmt;
+
+use concrete_commons::dispersion::{DispersionParameter, LogStandardDev, StandardDev};
+use concrete_commons::parameters::{DecompositionBaseLog, DecompositionLevelCount, GlweSize, PolynomialSize};
+use crate::*;
+
+/// The errors returned when a set of parameters is not consistent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContextError {
+    /// The polynomial size N is not a power of two (or is smaller than 2).
+    PolynomialSizeNotPowerOfTwo(usize),
+    /// The number of parties k is 0.
+    NoParties,
+    /// The number of public key samples m is 0.
+    EmptyPublicKey,
+    /// The public keys hold m samples, fewer than the minimum (second field) of the security level.
+    PublicKeyTooSmall(usize, usize),
+    /// glwe_size holds n + 1, so it must be at least 2.
+    EmptyLweDimension,
+    /// The LWE dimension n is larger than the polynomial size N.
+    LweDimensionTooLarge(usize, usize),
+    /// A decomposition (named by the str) has a zero base log or a zero level count.
+    EmptyDecomposition(&'static str),
+    /// A decomposition (named by the str) uses more than the bits of the torus (last field).
+    DecompositionTooLarge(&'static str, usize, usize, usize),
+    /// The torus has neither 32 nor 64 bits.
+    UnsupportedTorus(usize),
+    /// The keys or ciphertexts are over a torus of T::BITS bits (second field), not over the one of
+    /// the context (first field).
+    TorusMismatch(usize, usize),
+    /// A standard deviation (named by the str) is not in (0, 1).
+    InvalidStandardDev(&'static str, f64),
+    /// There is no preset for this number of parties.
+    NoPreset(usize),
+    /// The modulus 2^log_modulus of a modulus switching is larger than 2^64.
+    ModulusTooLarge(usize),
+    /// The message modulus of a lookup table is not a power of two.
+    MessageModulusNotPowerOfTwo(usize),
+    /// The message modulus of a lookup table is larger than the polynomial size N (second field).
+    MessageModulusTooLarge(usize, usize),
+    /// A multi-value lookup table is built without any function.
+    NoLookupFunction,
+    /// A multi-value lookup table packs more functions than the maximum (second field) that fit
+    /// in the boxes of the test polynomial.
+    TooManyLookupFunctions(usize, usize),
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextError::PolynomialSizeNotPowerOfTwo(n) =>
+                write!(f, "the polynomial size must be a power of two, got {}", n),
+            ContextError::NoParties =>
+                write!(f, "the number of parties must be at least 1"),
+            ContextError::EmptyPublicKey =>
+                write!(f, "the public keys must hold at least one sample (m >= 1)"),
+            ContextError::PublicKeyTooSmall(m, min) =>
+                write!(f, "the public keys must hold at least {} samples, got m = {}", min, m),
+            ContextError::EmptyLweDimension =>
+                write!(f, "the LWE dimension must be at least 1 (glwe_size >= 2)"),
+            ContextError::LweDimensionTooLarge(n, poly_size) =>
+                write!(f, "the LWE dimension {} can not be larger than the polynomial size {}", n, poly_size),
+            ContextError::EmptyDecomposition(name) =>
+                write!(f, "the {} decomposition must have a base log and a level count of at least 1", name),
+            ContextError::DecompositionTooLarge(name, base_log, level_count, bits) =>
+                write!(f, "the {} decomposition uses {} * {} bits, more than {}", name, base_log, level_count, bits),
+            ContextError::UnsupportedTorus(bits) =>
+                write!(f, "the torus must have 32 or 64 bits, got {}", bits),
+            ContextError::TorusMismatch(expected, found) =>
+                write!(f, "the context is over a torus of {} bits, got {} bits", expected, found),
+            ContextError::InvalidStandardDev(name, std) =>
+                write!(f, "the {} standard deviation must be in (0, 1), got {}", name, std),
+            ContextError::NoPreset(k) =>
+                write!(f, "there is no preset for {} parties", k),
+            ContextError::ModulusTooLarge(log_modulus) =>
+                write!(f, "the modulus 2^{} is larger than 2^{}", log_modulus, Scalar::BITS),
+            ContextError::MessageModulusNotPowerOfTwo(modulus) =>
+                write!(f, "the message modulus must be a power of two, got {}", modulus),
+            ContextError::MessageModulusTooLarge(modulus, poly_size) =>
+                write!(f, "the message modulus {} can not be larger than the polynomial size {}", modulus, poly_size),
+            ContextError::NoLookupFunction =>
+                write!(f, "a multi-value lookup table needs at least one function"),
+            ContextError::TooManyLookupFunctions(count, max) =>
+                write!(f, "{} functions do not fit in the lookup table, at most {} do", count, max),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+/// The seeds of the random generators of a `Context`, `encryption` and `noise` seed the mask and
+/// the noise streams of the `EncryptionRandomGenerator`. The noise stream is only seeded in replay
+/// mode (see `ContextBuilder::replay`), otherwise it is drawn from the system entropy.
+/// The seeds determine the secret keys, so they are as sensitive as the keys and must not be
+/// logged outside of replay mode. In replay mode, building a context with the same parameters and
+/// `ContextBuilder::seeds` yields the same secret keys and the same ciphertexts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seeds {
+    pub random: u128,
+    pub secret: u128,
+    pub encryption: u128,
+    pub noise: u128,
+}
+
+impl Seeds {
+    /// Derive the seeds from a single master seed.
+    pub fn from_master(master_seed: u128) -> Seeds {
+        let mut generator = RandomGenerator::new(Some(master_seed));
+        Seeds::from_generator(&mut generator)
+    }
+
+    /// Draw fresh seeds from the system entropy.
+    pub fn fresh() -> Seeds {
+        let mut generator = RandomGenerator::new(None);
+        Seeds::from_generator(&mut generator)
+    }
+
+    /// Draw the seeds from generator, e.g. to give every simulated party its own generators.
+    pub fn from_generator(generator: &mut RandomGenerator) -> Seeds {
+        Seeds {
+            random: random_seed(generator),
+            secret: random_seed(generator),
+            encryption: random_seed(generator),
+            noise: random_seed(generator),
+        }
+    }
+}
+
+/// Draw a u128 seed from generator.
+/// concrete-core reads a u128 sample from an unaligned byte buffer, which is undefined behaviour
+/// (and aborts the debug builds), so the 16 bytes are drawn one by one. The seed is the same as
+/// with `random_uniform::<u128>`.
+pub(crate) fn random_seed(generator: &mut RandomGenerator) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes.iter_mut().for_each(|b| *b = generator.random_uniform());
+    u128::from_ne_bytes(bytes)
+}
+
+/// The security level of a preset. Only the parameters of the paper are provided, `Insecure`
+/// is meant for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityLevel {
+    /// The parameters of the paper: n = 494, N = 2048.
+    Default,
+    /// Small parameters that run fast but provide NO security. Only use them for debugging.
+    Insecure,
+}
+
+impl SecurityLevel {
+    /// The statistical security, in bits, of the public key encryption: the binary combination of
+    /// the m public key samples must have log2(q) + bits bits of entropy, see `ContextBuilder::validate`.
+    pub fn public_key_bits(&self) -> usize {
+        match self {
+            SecurityLevel::Default => 128,
+            SecurityLevel::Insecure => 0,
+        }
+    }
+}
+
+/// A builder for `Context`.
+/// Every parameter starts at the value used by `Context::default()`, `build` checks that the
+/// parameters are consistent before creating the random generators.
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    std: LogStandardDev,
+    std_lwe: LogStandardDev,
+    std_rlwe: StandardDev,
+    poly_size: PolynomialSize,
+    base_log: DecompositionBaseLog,
+    level_count: DecompositionLevelCount,
+    ks_base_log: DecompositionBaseLog,
+    ks_level_count: DecompositionLevelCount,
+    rgsw_base_log: DecompositionBaseLog,
+    rgsw_level_count: DecompositionLevelCount,
+    negs_base_log: DecompositionBaseLog,
+    negs_level_count: DecompositionLevelCount,
+    m: usize,
+    glwe_size: GlweSize,
+    k: usize,
+    torus_bits: usize,
+    security: SecurityLevel,
+    seeds: Option<Seeds>,
+    replay: bool,
+}
+
+impl ContextBuilder {
+    pub fn new() -> ContextBuilder {
+        ContextBuilder {
+            std: LogStandardDev::from_log_standard_dev(-55.),
+            std_lwe: LogStandardDev::from_log_standard_dev(-47.), //std = 2^17 / 2^64
+            std_rlwe: StandardDev::from_standard_dev(9.76908e-16), //std = 1.85*2^(4.2) / 2^64
+            poly_size: PolynomialSize(2048),
+            base_log: DecompositionBaseLog(4),
+            level_count: DecompositionLevelCount(15),
+            ks_base_log: DecompositionBaseLog(13),
+            ks_level_count: DecompositionLevelCount(3),
+            rgsw_base_log: DecompositionBaseLog(10),
+            rgsw_level_count: DecompositionLevelCount(5),
+            negs_base_log: DecompositionBaseLog(30),
+            negs_level_count: DecompositionLevelCount(2),
+            m: 3*64,
+            glwe_size: GlweSize(495),
+            k: 4,
+            torus_bits: Scalar::BITS as usize,
+            security: SecurityLevel::Default,
+            seeds: None,
+            replay: false,
+        }
+    }
+
+    /// Return a builder holding the parameters of ctx (the seeds are not copied, replay mode is).
+    pub fn from_context(ctx: &Context) -> ContextBuilder {
+        ContextBuilder {
+            std: ctx.std,
+            std_lwe: ctx.std_lwe,
+            std_rlwe: ctx.std_rlwe,
+            poly_size: ctx.poly_size,
+            base_log: ctx.base_log,
+            level_count: ctx.level_count,
+            ks_base_log: ctx.ks_base_log,
+            ks_level_count: ctx.ks_level_count,
+            rgsw_base_log: ctx.rgsw_base_log,
+            rgsw_level_count: ctx.rgsw_level_count,
+            negs_base_log: ctx.negs_base_log,
+            negs_level_count: ctx.negs_level_count,
+            m: ctx.m,
+            glwe_size: ctx.glwe_size,
+            k: ctx.k,
+            torus_bits: ctx.torus_bits,
+            security: ctx.security,
+            seeds: None,
+            replay: ctx.replay,
+        }
+    }
+
+    /// Return a builder with the preset parameters for k parties.
+    /// The RGSW decomposition of the bootstrapping key of every range of k is the one picked from
+    /// the measurements in testing/ (see testing/bsk_size.txt). It is not monotone in k, e.g.
+    /// 5..=8 uses a smaller decomposition than 3..=4, so it should not be interpolated.
+    pub fn preset(k: usize, security: SecurityLevel) -> Result<ContextBuilder, ContextError> {
+        let (rgsw_base_log, rgsw_level_count) = match k {
+            1 | 2 => (12, 3),
+            3 | 4 => (10, 5),
+            5..=8 => (8, 4),
+            9..=16 => (10, 5),
+            17..=32 => (7, 6),
+            _ => return Err(ContextError::NoPreset(k)),
+        };
+        let builder = ContextBuilder::new()
+            .k(k)
+            .rgsw_base_log(DecompositionBaseLog(rgsw_base_log))
+            .rgsw_level_count(DecompositionLevelCount(rgsw_level_count));
+        Ok(match security {
+            SecurityLevel::Default => builder,
+            SecurityLevel::Insecure => builder
+                .poly_size(PolynomialSize(512))
+                .glwe_size(GlweSize(65))
+                .m(64)
+                .security(SecurityLevel::Insecure),
+        })
+    }
+
+    pub fn std(mut self, std: LogStandardDev) -> Self {
+        self.std = std;
+        self
+    }
+
+    pub fn std_lwe(mut self, std_lwe: LogStandardDev) -> Self {
+        self.std_lwe = std_lwe;
+        self
+    }
+
+    pub fn std_rlwe(mut self, std_rlwe: StandardDev) -> Self {
+        self.std_rlwe = std_rlwe;
+        self
+    }
+
+    pub fn poly_size(mut self, poly_size: PolynomialSize) -> Self {
+        self.poly_size = poly_size;
+        self
+    }
+
+    pub fn base_log(mut self, base_log: DecompositionBaseLog) -> Self {
+        self.base_log = base_log;
+        self
+    }
+
+    pub fn level_count(mut self, level_count: DecompositionLevelCount) -> Self {
+        self.level_count = level_count;
+        self
+    }
+
+    pub fn ks_base_log(mut self, ks_base_log: DecompositionBaseLog) -> Self {
+        self.ks_base_log = ks_base_log;
+        self
+    }
+
+    pub fn ks_level_count(mut self, ks_level_count: DecompositionLevelCount) -> Self {
+        self.ks_level_count = ks_level_count;
+        self
+    }
+
+    pub fn rgsw_base_log(mut self, rgsw_base_log: DecompositionBaseLog) -> Self {
+        self.rgsw_base_log = rgsw_base_log;
+        self
+    }
+
+    pub fn rgsw_level_count(mut self, rgsw_level_count: DecompositionLevelCount) -> Self {
+        self.rgsw_level_count = rgsw_level_count;
+        self
+    }
+
+    pub fn negs_base_log(mut self, negs_base_log: DecompositionBaseLog) -> Self {
+        self.negs_base_log = negs_base_log;
+        self
+    }
+
+    pub fn negs_level_count(mut self, negs_level_count: DecompositionLevelCount) -> Self {
+        self.negs_level_count = negs_level_count;
+        self
+    }
+
+    /// Set the number of samples of the public keys.
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m;
+        self
+    }
+
+    /// Set the size of the LWE ciphertexts, i.e. n + 1.
+    pub fn glwe_size(mut self, glwe_size: GlweSize) -> Self {
+        self.glwe_size = glwe_size;
+        self
+    }
+
+    /// Set the number of parties.
+    pub fn k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Set the number of bits of the torus, 64 (`Scalar`) by default or 32. The keys and the
+    /// ciphertexts used with the context must be over a T with T::BITS = torus_bits, see
+    /// `Context::check_torus`. The decompositions and m are checked against it.
+    pub fn torus_bits(mut self, torus_bits: usize) -> Self {
+        self.torus_bits = torus_bits;
+        self
+    }
+
+    /// Set the security level the public key size m is checked against.
+    pub fn security(mut self, security: SecurityLevel) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Seed the random generators with the given seeds.
+    pub fn seeds(mut self, seeds: Seeds) -> Self {
+        self.seeds = Some(seeds);
+        self
+    }
+
+    /// Seed the random generators with seeds derived from master_seed.
+    pub fn seed(self, master_seed: u128) -> Self {
+        self.seeds(Seeds::from_master(master_seed))
+    }
+
+    /// Seed the noise stream of the encryptions too, so that a run can be replayed bit-for-bit.
+    /// Every error term is then recomputable from the seeds: only use it for testing and debugging.
+    /// It needs concrete-core-seed-noise.patch on top of the bundled concrete-core, see the README.
+    #[cfg(feature = "replay")]
+    pub fn replay(mut self, replay: bool) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Check the invariants the rest of the crate relies on.
+    pub fn validate(&self) -> Result<(), ContextError> {
+        if self.poly_size.0 < 2 || !self.poly_size.0.is_power_of_two() {
+            return Err(ContextError::PolynomialSizeNotPowerOfTwo(self.poly_size.0));
+        }
+        if self.k == 0 {
+            return Err(ContextError::NoParties);
+        }
+        if self.torus_bits != 32 && self.torus_bits != 64 {
+            return Err(ContextError::UnsupportedTorus(self.torus_bits));
+        }
+        if self.m == 0 {
+            return Err(ContextError::EmptyPublicKey);
+        }
+        // A public key encryption sums a random binary combination of the m samples, it must have
+        // enough entropy to hide the body of the public key (leftover hash lemma).
+        let min_m = self.torus_bits + self.security.public_key_bits();
+        if self.m < min_m {
+            return Err(ContextError::PublicKeyTooSmall(self.m, min_m));
+        }
+        if self.glwe_size.0 < 2 {
+            return Err(ContextError::EmptyLweDimension);
+        }
+        // The keyswitching key goes from the extracted key of size N down to n.
+        if self.glwe_size.0 - 1 > self.poly_size.0 {
+            return Err(ContextError::LweDimensionTooLarge(self.glwe_size.0 - 1, self.poly_size.0));
+        }
+
+        let decompositions = [
+            ("gadget", self.base_log, self.level_count),
+            ("keyswitch", self.ks_base_log, self.ks_level_count),
+            ("rgsw", self.rgsw_base_log, self.rgsw_level_count),
+            ("negs", self.negs_base_log, self.negs_level_count),
+        ];
+        for (name, base_log, level_count) in decompositions {
+            if base_log.0 == 0 || level_count.0 == 0 {
+                return Err(ContextError::EmptyDecomposition(name));
+            }
+            if base_log.0 * level_count.0 > self.torus_bits {
+                return Err(ContextError::DecompositionTooLarge(name, base_log.0, level_count.0, self.torus_bits));
+            }
+        }
+
+        let stds = [
+            ("std", self.std.get_standard_dev()),
+            ("lwe", self.std_lwe.get_standard_dev()),
+            ("rlwe", self.std_rlwe.get_standard_dev()),
+        ];
+        for (name, std) in stds {
+            if !(std > 0. && std < 1.) {
+                return Err(ContextError::InvalidStandardDev(name, std));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<Context, ContextError> {
+        self.validate()?;
+        // Without explicit seeds we still draw them, so that they can be reused in replay mode.
+        let seeds = self.seeds.unwrap_or_else(Seeds::fresh);
+        Ok(Context {
+            random_generator: RandomGenerator::new(Some(seeds.random)),
+            secret_generator: SecretRandomGenerator::new(Some(seeds.secret)),
+            encryption_generator: self.encryption_generator(&seeds),
+            seeds,
+            replay: self.replay,
+            std: self.std,
+            std_lwe: self.std_lwe,
+            std_rlwe: self.std_rlwe,
+            poly_size: self.poly_size,
+            base_log: self.base_log,
+            level_count: self.level_count,
+            ks_base_log: self.ks_base_log,
+            ks_level_count: self.ks_level_count,
+            rgsw_base_log: self.rgsw_base_log,
+            rgsw_level_count: self.rgsw_level_count,
+            negs_base_log: self.negs_base_log,
+            negs_level_count: self.negs_level_count,
+            m: self.m,
+            glwe_size: self.glwe_size,
+            k: self.k,
+            torus_bits: self.torus_bits,
+            security: self.security,
+        })
+    }
+
+    #[cfg(feature = "replay")]
+    fn encryption_generator(&self, seeds: &Seeds) -> EncryptionRandomGenerator {
+        let mut generator = EncryptionRandomGenerator::new(Some(seeds.encryption));
+        if self.replay {
+            generator.seed_noise_generator(seeds.noise);
+        }
+        generator
+    }
+
+    #[cfg(not(feature = "replay"))]
+    fn encryption_generator(&self, seeds: &Seeds) -> EncryptionRandomGenerator {
+        EncryptionRandomGenerator::new(Some(seeds.encryption))
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        ContextBuilder::new()
+    }
+}
+
+/// The predicted heap sizes, in bytes, of the keys generated with a parameter set, see
+/// `Context::key_size_report`. They match `GetSize::get_heap_size` of the generated keys up to
+/// the headers of the vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySizeReport {
+    pub lwe_public_key: usize,
+    pub compressed_lwe_public_key: usize,
+    pub rlwe_public_key: usize,
+    pub compressed_rlwe_public_key: usize,
+    pub keyswitch_key: usize,
+    pub compressed_keyswitch_key: usize,
+    /// The n RGSW encryptions of the key bits sent by every party. They are public key
+    /// encryptions, so there is no compressed form (see `BootstrappingKeyShare`, compressing the
+    /// bootstrapping key is out of scope).
+    pub bootstrapping_key_share: usize,
+    /// The n indicator lists of k + 1 RGSW ciphertexts, it grows linearly with k.
+    pub bootstrapping_key: usize,
+    /// The bootstrapping key in the Fourier domain as held by `ServerKey`, with the 2N monomials.
+    pub fourier_bootstrapping_key: usize,
+}
+
+impl KeySizeReport {
+    pub fn from_context(ctx: &Context) -> KeySizeReport {
+        let scalar = ctx.torus_bits / 8;
+        let complex = std::mem::size_of::<Complex64>();
+        let n = ctx.glwe_size.0 - 1;
+        let poly_size = ctx.poly_size.0;
+
+        let rgsw = 2 * ctx.rgsw_level_count.0 * 2 * poly_size * scalar;
+        let fourier_rgsw = ctx.rgsw_level_count.0 * 2 * 2 * poly_size * complex;
+        let monomials = 2 * poly_size * poly_size * complex;
+        let ksk_count = poly_size * ctx.ks_level_count.0;
+
+        KeySizeReport {
+            lwe_public_key: ctx.m * (n + 1) * scalar,
+            compressed_lwe_public_key: ctx.m * scalar,
+            rlwe_public_key: ctx.m * 2 * poly_size * scalar,
+            compressed_rlwe_public_key: ctx.m * poly_size * scalar,
+            keyswitch_key: ksk_count * (n + 1) * scalar,
+            compressed_keyswitch_key: ksk_count * scalar,
+            bootstrapping_key_share: n * rgsw,
+            bootstrapping_key: n * (ctx.k + 1) * rgsw,
+            fourier_bootstrapping_key: n * (ctx.k + 1) * fourier_rgsw + monomials,
+        }
+    }
+}
+
+impl fmt::Display for KeySizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024. * 1024.);
+        writeln!(f, "LWE public key:            {:>10.2} MiB ({:.2} MiB compressed)", mib(self.lwe_public_key), mib(self.compressed_lwe_public_key))?;
+        writeln!(f, "RLWE public key:           {:>10.2} MiB ({:.2} MiB compressed)", mib(self.rlwe_public_key), mib(self.compressed_rlwe_public_key))?;
+        writeln!(f, "Keyswitching key:          {:>10.2} MiB ({:.2} MiB compressed)", mib(self.keyswitch_key), mib(self.compressed_keyswitch_key))?;
+        writeln!(f, "Bootstrapping key share:   {:>10.2} MiB per party", mib(self.bootstrapping_key_share))?;
+        writeln!(f, "Bootstrapping key:         {:>10.2} MiB", mib(self.bootstrapping_key))?;
+        write!(f, "Fourier bootstrapping key: {:>10.2} MiB", mib(self.fourier_bootstrapping_key))
+    }
+}
@@ -0,0 +1,377 @@
+use concrete_commons::dispersion::DispersionParameter;
+use concrete_commons::parameters::{GlweSize, LweDimension};
+use crate::*;
+use crate::lwe::{combine_shares, par_generate_bootstrapping_key, CompressedLWEKeyswitchKey, CompressedLWEPublicKey, DecryptionShare, LWECiphertext, LWEKeyswitchKey, LWEPublicKey, LWESecretKey, LWEtoRLWEKeyswitchKey};
+use crate::rgsw::RGSWCiphertext;
+use crate::server_key::ServerKey;
+use crate::circuit_bootstrap::CircuitBootstrappingKey;
+use crate::error::MfheError;
+use crate::params::random_seed;
+use concrete_core::backends::core::private::math::torus::UnsignedTorus;
+use concrete_core::backends::core::private::crypto::encoding::Plaintext;
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+// The key generation runs in the following rounds:
+// 1. the coordinator publishes a seed (`CommonReferenceString`) from which everybody expands the
+//    common masks (`CommonReference`),
+// 2. every party sends a `PublicKeyShare`, the coordinator checks its masks against the expanded
+//    ones and sums them into the `JointPublicKey`,
+// 3. every party sends a `BootstrappingKeyShare` encrypted under the joint public key, the
+//    coordinator turns them into the `ServerKey`.
+// Decryption is done by summing the `DecryptionShare` of every party.
+// Only public messages go from the parties to the coordinator.
+
+/// The seed of the common masks. It is the only message of the first round: every party expands
+/// it locally into the `CommonReference` instead of receiving m * N mask coefficients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommonReferenceString {
+    seed: u128,
+}
+
+impl CommonReferenceString {
+    /// Draw a fresh seed from generator.
+    pub fn generate(generator: &mut RandomGenerator) -> CommonReferenceString {
+        CommonReferenceString { seed: random_seed(generator) }
+    }
+
+    pub fn from_seed(seed: u128) -> CommonReferenceString {
+        CommonReferenceString { seed }
+    }
+
+    pub fn seed(&self) -> u128 {
+        self.seed
+    }
+
+    /// Derive the mask seeds of the RLWE public key, of the LWE public key and of the keyswitching
+    /// key, in this order. Every key gets its own seed so that it can be compressed on its own.
+    pub fn mask_seeds(&self) -> (u128, u128, u128) {
+        let mut generator = RandomGenerator::new(Some(self.seed));
+        (random_seed(&mut generator), random_seed(&mut generator), random_seed(&mut generator))
+    }
+
+    /// Expand the seed into the masks of the RLWE public key, of the LWE public key and of the
+    /// keyswitching key. Only the mask stream of the seeded generators is used, so the expansion
+    /// is the same for every party.
+    pub fn expand<T: UnsignedTorus>(&self, ctx: &Context) -> CommonReference<T> {
+        let (rlwe_pk_seed, lwe_pk_seed, ksk_seed) = self.mask_seeds();
+
+        let mut rlwe_pk = RLWEPublicKey::allocate(ctx.poly_size, GlweSize(2), ctx.m);
+        let mut lwe_pk = LWEPublicKey::allocate(ctx.glwe_size.0, ctx.m);
+        let mut ksk = LWEKeyswitchKey::allocate(LweDimension(ctx.poly_size.0), LweDimension(ctx.glwe_size.0 - 1), ctx);
+
+        rlwe_pk.fill_mask_from_seed(rlwe_pk_seed);
+        lwe_pk.fill_mask_from_seed(lwe_pk_seed);
+        ksk.fill_mask_from_seed(ksk_seed);
+
+        CommonReference { rlwe_pk, lwe_pk, ksk }
+    }
+}
+
+/// The common masks of the public keys and of the keyswitching key, expanded from a
+/// `CommonReferenceString`.
+#[derive(Debug, Clone)]
+pub struct CommonReference<T = Scalar> {
+    pub rlwe_pk: RLWEPublicKey<T>,
+    pub lwe_pk: LWEPublicKey<T>,
+    pub ksk: LWEKeyswitchKey<T>,
+}
+
+impl<T: UnsignedTorus> CommonReference<T> {
+    /// Return true if the masks of share are the common ones.
+    pub fn check_share(&self, share: &PublicKeyShare<T>) -> bool {
+        self.rlwe_pk.compare_mask(&share.rlwe_pk, self.rlwe_pk.get_size())
+            && self.lwe_pk.compare_mask(&share.lwe_pk, self.lwe_pk.get_size())
+            && self.ksk.compare_mask(&share.ksk)
+    }
+
+    /// Return true if the masks of joint_public_key are the common ones.
+    pub fn check_joint_public_key(&self, joint_public_key: &JointPublicKey<T>) -> bool {
+        self.rlwe_pk.compare_mask(&joint_public_key.rlwe_pk, self.rlwe_pk.get_size())
+            && self.lwe_pk.compare_mask(&joint_public_key.lwe_pk, self.lwe_pk.get_size())
+    }
+}
+
+/// The contribution of a party to the public keys and to the keyswitching key.
+/// The masks are the ones of the `CommonReference`, the bodies depend on the keys of the party.
+#[derive(Debug, Clone)]
+pub struct PublicKeyShare<T = Scalar> {
+    pub party_id: usize,
+    pub rlwe_pk: RLWEPublicKey<T>,
+    pub lwe_pk: LWEPublicKey<T>,
+    pub ksk: LWEKeyswitchKey<T>,
+}
+
+impl<T: UnsignedTorus> PublicKeyShare<T> {
+    /// Keep only the bodies, the masks are the ones expanded from crs.
+    pub fn compress(&self, crs: &CommonReferenceString) -> CompressedPublicKeyShare<T> {
+        let (rlwe_pk_seed, lwe_pk_seed, ksk_seed) = crs.mask_seeds();
+        CompressedPublicKeyShare {
+            party_id: self.party_id,
+            rlwe_pk: self.rlwe_pk.compress(rlwe_pk_seed),
+            lwe_pk: self.lwe_pk.compress(lwe_pk_seed),
+            ksk: self.ksk.compress(ksk_seed),
+        }
+    }
+}
+
+/// A `PublicKeyShare` without its masks, the form in which it is sent to the coordinator.
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+pub struct CompressedPublicKeyShare<T = Scalar> {
+    pub party_id: usize,
+    pub rlwe_pk: CompressedRLWEPublicKey<T>,
+    pub lwe_pk: CompressedLWEPublicKey<T>,
+    pub ksk: CompressedLWEKeyswitchKey<T>,
+}
+
+impl<T: UnsignedTorus> CompressedPublicKeyShare<T> {
+    /// Regenerate the masks, the sizes of every part are checked against ctx.
+    pub fn decompress(&self, ctx: &Context) -> Result<PublicKeyShare<T>, MfheError> {
+        Ok(PublicKeyShare {
+            party_id: self.party_id,
+            rlwe_pk: self.rlwe_pk.decompress(ctx)?,
+            lwe_pk: self.lwe_pk.decompress(ctx)?,
+            ksk: self.ksk.decompress(ctx)?,
+        })
+    }
+}
+
+/// The public keys of the joint secret keys.
+#[derive(Debug, Clone)]
+pub struct JointPublicKey<T = Scalar> {
+    pub rlwe_pk: RLWEPublicKey<T>,
+    pub lwe_pk: LWEPublicKey<T>,
+}
+
+/// The RGSW encryptions, under the joint RLWE public key, of the LWE key of a party.
+/// Unlike the `CompressedPublicKeyShare` it is sent in full: the masks of a public key encryption
+/// are combinations of the masks of the joint public key with the secret randomness of the party,
+/// they can not be regenerated from a seed. Compressing the bootstrapping key is out of scope:
+/// the RGSW ciphertexts encrypted under a secret key (`RLWESecretKey::encrypt_rgsw`) could keep
+/// only a seed and their bodies, but they are not part of the protocol and are stored in full too.
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+pub struct BootstrappingKeyShare<T = Scalar>(pub Vec<RGSWCiphertext<T>>);
+
+/// The contribution of a party to the `CircuitBootstrappingKey`, under the joint RLWE public key.
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+pub struct CircuitBootstrappingKeyShare<T = Scalar> {
+    pub party_id: usize,
+    /// The RGSW encryptions of the bits of the LWE key of the party, with the keyswitching
+    /// decomposition.
+    pub lwe_to_rlwe_ksk: Vec<RGSWCiphertext<T>>,
+    /// RGSW(-s_i) for the RLWE key s_i of the party.
+    pub neg_s: RGSWCiphertext<T>,
+}
+
+/// A party of the protocol. It owns its secret keys and its random generators (in its own
+/// `Context`) and only outputs public messages. The keys are over the torus of T, which must be
+/// the one of the context (see `Context::check_torus`).
+pub struct Party<T = Scalar> {
+    id: usize,
+    ctx: Context,
+    lwe_sk: LWESecretKey<T>,
+    rlwe_sk: RLWESecretKey<T>,
+    crs: Option<CommonReference<T>>,
+    joint_public_key: Option<JointPublicKey<T>>,
+}
+
+impl<T: UnsignedTorus + Send + Sync> Party<T> {
+    /// Generate the secret keys of the party with id. The party uses the parameters of ctx, its
+    /// generators are seeded from its own entropy (see `Context::fresh`).
+    pub fn new(id: usize, ctx: &Context) -> Result<Party<T>, MfheError> {
+        Party::with_context(id, ctx.fresh())
+    }
+
+    /// Same as `new` with ctx as the context of the party, e.g. a context seeded for replay. The
+    /// seeds of ctx determine the secret keys, so ctx must not be known to anybody else.
+    pub fn with_context(id: usize, mut ctx: Context) -> Result<Party<T>, MfheError> {
+        ctx.check_torus::<T>()?;
+        let lwe_sk = LWESecretKey::generate_binary(LweDimension(ctx.glwe_size.0 - 1), &mut ctx.secret_generator);
+        let rlwe_sk = RLWESecretKey::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
+        Ok(Party { id, ctx, lwe_sk, rlwe_sk, crs: None, joint_public_key: None })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Round 2: expand the common masks from crs and fill the bodies of the public keys and of the
+    /// keyswitching key over them.
+    pub fn public_key_share(&mut self, crs: &CommonReferenceString) -> PublicKeyShare<T> {
+        let common = crs.expand(&self.ctx);
+        let ctx = &mut self.ctx;
+
+        let mut rlwe_pk = common.rlwe_pk.clone();
+        let mut lwe_pk = common.lwe_pk.clone();
+
+        self.rlwe_sk.fill_rlwe_pk(&mut rlwe_pk, ctx.std_rlwe, &mut ctx.encryption_generator, ctx.m);
+        self.lwe_sk.fill_lwe_pk(&mut lwe_pk, ctx.std_lwe, &mut ctx.encryption_generator, ctx.m);
+
+        let extracted_sk = LWESecretKey::from_concrete_sk(self.rlwe_sk.clone().to_lwe_secretkey(ctx));
+        let mut ksk = common.ksk.clone();
+        self.lwe_sk.fill_ksk_share(&mut ksk, &extracted_sk, ctx.std_rlwe, &mut ctx.encryption_generator);
+
+        self.crs = Some(common);
+        PublicKeyShare { party_id: self.id, rlwe_pk, lwe_pk, ksk }
+    }
+
+    /// Check the joint public key broadcast by the coordinator against the common masks expanded
+    /// in round 2 and store it.
+    pub fn receive_joint_public_key(&mut self, joint_public_key: &JointPublicKey<T>) -> Result<(), MfheError> {
+        let crs = self.crs.as_ref().ok_or(MfheError::MissingKeyMaterial("common reference"))?;
+        if !crs.check_joint_public_key(joint_public_key) {
+            return Err(MfheError::InconsistentJointPublicKey);
+        }
+        self.joint_public_key = Some(joint_public_key.clone());
+        Ok(())
+    }
+
+    /// Round 3: encrypt the bits of the LWE key under the joint RLWE public key, in parallel (see
+    /// `LWESecretKey::par_encrypt_rgsw_bits`).
+    pub fn bootstrapping_key_share(&mut self) -> Result<BootstrappingKeyShare<T>, MfheError> {
+        let joint_public_key = self.joint_public_key.as_ref()
+            .ok_or(MfheError::MissingKeyMaterial("joint public key"))?;
+        Ok(BootstrappingKeyShare(self.lwe_sk.par_encrypt_rgsw_bits(&joint_public_key.rlwe_pk, &mut self.ctx)?))
+    }
+
+    /// Optional round 4: the share of the key needed by `circuit_bootstrap`. Like the bootstrapping
+    /// key share it only uses the joint RLWE public key, the LWE key bits are encrypted in parallel.
+    pub fn circuit_bootstrapping_key_share(&mut self) -> Result<CircuitBootstrappingKeyShare<T>, MfheError> {
+        let joint_public_key = self.joint_public_key.as_ref()
+            .ok_or(MfheError::MissingKeyMaterial("joint public key"))?;
+        let (base_log, level_count) = (self.ctx.ks_base_log, self.ctx.ks_level_count);
+        let lwe_to_rlwe_ksk = self.lwe_sk.par_encrypt_rgsw_bits_with_decomposition(&joint_public_key.rlwe_pk, base_log, level_count, &mut self.ctx)?;
+        let neg_s = self.rlwe_sk.neg_gsw_with_public_key(&joint_public_key.rlwe_pk, &mut self.ctx)?;
+        Ok(CircuitBootstrappingKeyShare { party_id: self.id, lwe_to_rlwe_ksk, neg_s })
+    }
+
+    /// Compute the share of the party for the threshold decryption of ct.
+    pub fn decryption_share(&mut self, ct: &LWECiphertext<T>, smudging_noise: impl DispersionParameter) -> DecryptionShare<T> {
+        self.lwe_sk.partial_decrypt(ct, self.id, smudging_noise, &mut self.ctx.random_generator)
+    }
+
+    /// Return the LWE secret key of the party. It must never leave the party, it is only exposed
+    /// to the crate to measure the noise in simulations (see `NoiseProfiler`).
+    pub(crate) fn lwe_secret_key(&self) -> &LWESecretKey<T> {
+        &self.lwe_sk
+    }
+
+    /// Return the RLWE secret key of the party, with the same restrictions as `lwe_secret_key`.
+    pub(crate) fn rlwe_secret_key(&self) -> &RLWESecretKey<T> {
+        &self.rlwe_sk
+    }
+}
+
+/// The coordinator of the protocol. It generates the common masks and aggregates the messages of
+/// the parties, it never receives any secret.
+pub struct Coordinator<T = Scalar> {
+    ctx: Context,
+    crs_seed: CommonReferenceString,
+    crs: CommonReference<T>,
+    joint_public_key: Option<JointPublicKey<T>>,
+    ksk: Option<LWEKeyswitchKey<T>>,
+}
+
+impl<T: UnsignedTorus + Send + Sync> Coordinator<T> {
+    /// Round 1: draw the seed of the common masks. The coordinator uses the parameters of ctx, its
+    /// generators are seeded from its own entropy (see `Context::fresh`).
+    pub fn new(ctx: &Context) -> Result<Coordinator<T>, MfheError> {
+        let mut ctx = ctx.fresh();
+        let crs_seed = CommonReferenceString::generate(&mut ctx.random_generator);
+        Coordinator::with_common_reference_string(ctx, crs_seed)
+    }
+
+    /// Use crs_seed, e.g. a seed agreed on beforehand, instead of drawing a fresh one.
+    pub fn with_common_reference_string(ctx: Context, crs_seed: CommonReferenceString) -> Result<Coordinator<T>, MfheError> {
+        ctx.check_torus::<T>()?;
+        let crs = crs_seed.expand(&ctx);
+        Ok(Coordinator {
+            ctx,
+            crs_seed,
+            crs,
+            joint_public_key: None,
+            ksk: None,
+        })
+    }
+
+    /// The seed to broadcast to the parties.
+    pub fn common_reference_string(&self) -> CommonReferenceString {
+        self.crs_seed
+    }
+
+    pub fn common_reference(&self) -> &CommonReference<T> {
+        &self.crs
+    }
+
+    /// Round 2: sum the bodies of the shares over the common masks. There must be exactly one share
+    /// per party id in [0, k) and the masks of every share are checked against the common reference
+    /// first, nothing is aggregated if one of them differs.
+    pub fn aggregate_public_keys(&mut self, shares: &[PublicKeyShare<T>]) -> Result<&JointPublicKey<T>, MfheError> {
+        check_party_ids("list of public key shares", shares.iter().map(|share| share.party_id), self.ctx.k)?;
+        if let Some(share) = shares.iter().find(|share| !self.crs.check_share(share)) {
+            return Err(MfheError::InconsistentPublicKeyShare(share.party_id));
+        }
+
+        let mut rlwe_pk = self.crs.rlwe_pk.clone();
+        let mut lwe_pk = self.crs.lwe_pk.clone();
+        let mut ksk = self.crs.ksk.clone();
+
+        for share in shares {
+            rlwe_pk.update_body_with_pk(&share.rlwe_pk, &self.ctx);
+            lwe_pk.update_body_with_pk(&share.lwe_pk, &self.ctx);
+            ksk.update_body_with_share(&share.ksk);
+        }
+
+        self.ksk = Some(ksk);
+        Ok(self.joint_public_key.insert(JointPublicKey { rlwe_pk, lwe_pk }))
+    }
+
+    pub fn joint_public_key(&self) -> Option<&JointPublicKey<T>> {
+        self.joint_public_key.as_ref()
+    }
+
+    /// Round 3: compute the indicator lists from the shares of the parties and build the server key.
+    /// The LWE coordinates (and the internal products of every round) are spread over the rayon
+    /// thread pool, see `par_generate_bootstrapping_key`.
+    /// There must be one share per party, each holding one RGSW ciphertext per LWE coordinate.
+    pub fn aggregate_bootstrapping_key(&mut self, shares: &[BootstrappingKeyShare<T>]) -> Result<ServerKey<T>, MfheError> {
+        let ksk = self.ksk.clone().ok_or(MfheError::MissingKeyMaterial("keyswitching key"))?;
+        if shares.len() != self.ctx.k {
+            return Err(MfheError::DimensionMismatch { what: "list of bootstrapping key shares", expected: self.ctx.k, found: shares.len() });
+        }
+        let lwe_dimension = self.ctx.glwe_size.0 - 1;
+        if let Some(share) = shares.iter().find(|share| share.0.len() != lwe_dimension) {
+            return Err(MfheError::DimensionMismatch { what: "bootstrapping key share", expected: lwe_dimension, found: share.0.len() });
+        }
+        let party_bits: Vec<Vec<RGSWCiphertext<T>>> = shares.iter().map(|share| share.0.clone()).collect();
+        let bootstrapping_key = par_generate_bootstrapping_key(&party_bits)?;
+        ServerKey::new(bootstrapping_key, ksk)
+    }
+
+    /// Round 4: sum the circuit bootstrapping key shares of all the parties into the key of
+    /// `circuit_bootstrap`. The RGSW encryptions are additive, so no product is needed.
+    pub fn aggregate_circuit_bootstrapping_key(&self, shares: &[CircuitBootstrappingKeyShare<T>]) -> Result<CircuitBootstrappingKey<T>, MfheError> {
+        check_party_ids("list of circuit bootstrapping key shares", shares.iter().map(|share| share.party_id), self.ctx.k)?;
+        let lwe_dimension = self.ctx.glwe_size.0 - 1;
+        if let Some(share) = shares.iter().find(|share| share.lwe_to_rlwe_ksk.len() != lwe_dimension) {
+            return Err(MfheError::DimensionMismatch { what: "circuit bootstrapping key share", expected: lwe_dimension, found: share.lwe_to_rlwe_ksk.len() });
+        }
+
+        let ksk_shares: Vec<&[RGSWCiphertext<T>]> = shares.iter().map(|share| share.lwe_to_rlwe_ksk.as_slice()).collect();
+        let mut neg_s = shares[0].neg_s.clone();
+        for share in shares[1..].iter() {
+            neg_s.add_ciphertext(&share.neg_s);
+        }
+        Ok(CircuitBootstrappingKey { lwe_to_rlwe_ksk: LWEtoRLWEKeyswitchKey::from_shares(&ksk_shares)?, neg_s })
+    }
+
+    /// Recover the plaintext of ct from the decryption shares of all the parties, there must be
+    /// exactly one share per party.
+    pub fn combine_decryption_shares(&self, ct: &LWECiphertext<T>, shares: &[DecryptionShare<T>]) -> Result<Plaintext<T>, MfheError> {
+        combine_shares(ct, shares, self.ctx.k)
+    }
+}
@@ -0,0 +1,213 @@
+use std::io;
+
+use chrono::{DateTime, Local};
+use concrete_commons::parameters::LweSize;
+use concrete_core::backends::core::private as ccore;
+use ccore::crypto::encoding::Plaintext;
+use crate::*;
+use crate::gates::EIGHTH;
+use crate::lwe::{compute_noise_lwe, constant_sample_extract, LWECiphertext, LWEPublicKey, LWESecretKey};
+use crate::noise::{NoiseReport, NoiseStats};
+use crate::party::{JointPublicKey, Party};
+use crate::server_key::ServerKey;
+
+/// A stage of the gate bootstrapping at which the noise is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Encryption of an input bit with the joint LWE public key.
+    FreshEncryption,
+    /// The input of the bootstrapping, (0, 1/8) - ct1 - ct2 for NAND.
+    LinearCombination,
+    /// The linear combination switched to the modulus 2N, as done before the blind rotation.
+    ModulusSwitch,
+    /// The accumulator after the blind rotation.
+    BlindRotation,
+    /// The constant coefficient of the accumulator, under the joint RLWE key seen as an LWE key.
+    SampleExtraction,
+    /// The output of the gate, under the joint LWE key.
+    Keyswitch,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::FreshEncryption => "fresh_encryption",
+            Stage::LinearCombination => "linear_combination",
+            Stage::ModulusSwitch => "modulus_switch",
+            Stage::BlindRotation => "blind_rotation",
+            Stage::SampleExtraction => "sample_extraction",
+            Stage::Keyswitch => "keyswitch",
+        }
+    }
+}
+
+/// The noise measured at one stage of one run.
+#[derive(Debug, Clone)]
+pub struct NoiseRecord {
+    pub timestamp: DateTime<Local>,
+    pub run: usize,
+    pub stage: Stage,
+    /// log2 of the largest absolute error, in units of 2^-64.
+    pub log2_noise: f64,
+    /// log2 of the standard deviation predicted by `NoiseReport`, in units of 2^-64.
+    pub predicted_log2_std: f64,
+}
+
+/// Runs NAND gates with the keys of a simulated multiparty setup and records the noise after
+/// every stage (see `Stage`). The joint secret keys are the sums of the keys of the parties: they
+/// are only used to measure the noise, as in the simulations of `main`.
+pub struct NoiseProfiler<'a> {
+    label: String,
+    lwe_pk: &'a LWEPublicKey,
+    server_key: &'a ServerKey,
+    lwe_sk: LWESecretKey,
+    rlwe_sk: RLWESecretKey,
+    extracted_sk: LWESecretKey,
+    model: NoiseReport,
+    k: usize,
+    lwe_dimension: usize,
+    poly_size: usize,
+    runs: usize,
+    records: Vec<NoiseRecord>,
+}
+
+impl<'a> NoiseProfiler<'a> {
+    /// Build a profiler for the keys of parties. label names the parameter set in the CSV rows,
+    /// e.g. the preset the context was built from.
+    pub fn new(label: &str, parties: &[Party], joint_public_key: &'a JointPublicKey, server_key: &'a ServerKey, ctx: &Context) -> NoiseProfiler<'a> {
+        let lwe_dimension = ctx.glwe_size.0 - 1;
+        let mut lwe_sk = LWESecretKey::zero(lwe_dimension);
+        let mut rlwe_sk = RLWESecretKey::zero(ctx.poly_size);
+        for party in parties.iter() {
+            lwe_sk.as_mut_tensor().update_with_wrapping_add(party.lwe_secret_key().as_tensor());
+            rlwe_sk.as_mut_tensor().update_with_wrapping_add(party.rlwe_secret_key().as_tensor());
+        }
+        let mut extracted_sk = LWESecretKey::zero(ctx.poly_size.0);
+        extracted_sk.as_mut_tensor().fill_with_copy(rlwe_sk.as_tensor());
+
+        NoiseProfiler {
+            label: label.to_string(),
+            lwe_pk: &joint_public_key.lwe_pk,
+            server_key,
+            lwe_sk,
+            rlwe_sk,
+            extracted_sk,
+            model: ctx.noise_report(),
+            k: ctx.k,
+            lwe_dimension,
+            poly_size: ctx.poly_size.0,
+            runs: 0,
+            records: Vec::new(),
+        }
+    }
+
+    /// Run runs NAND gates and record their noise. The inputs cycle through the four pairs of
+    /// bits, so that every run of 4 covers the whole truth table.
+    pub fn run(&mut self, runs: usize, ctx: &mut Context) -> Result<(), MfheError> {
+        let log_2n = (2 * self.poly_size).trailing_zeros() as usize;
+        let model = &self.model;
+        let fresh = model.fresh_lwe;
+        let linear = 2. * fresh;
+        let switched = linear + model.modulus_switch;
+        let rotated = model.blind_rotation;
+        let keyswitched = model.blind_rotation + model.keyswitch;
+
+        for run in self.runs..self.runs + runs {
+            let (bit1, bit2) = ((run & 1) as Scalar, ((run >> 1) & 1) as Scalar);
+            let (mut pt1, mut pt2) = (bit1, bit2);
+            encode_gate(&mut pt1);
+            encode_gate(&mut pt2);
+            let mut expected = 1 - bit1 * bit2;
+            encode_gate(&mut expected);
+
+            let mut ct1 = LWECiphertext::allocate(LweSize(self.lwe_dimension + 1));
+            let mut ct2 = LWECiphertext::allocate(LweSize(self.lwe_dimension + 1));
+            self.lwe_pk.encrypt_lwe(&mut ct1, &Plaintext(pt1), &mut ctx.secret_generator, ctx.m)?;
+            self.lwe_pk.encrypt_lwe(&mut ct2, &Plaintext(pt2), &mut ctx.secret_generator, ctx.m)?;
+            let noise = compute_noise_lwe(&self.lwe_sk, &ct1, pt1, EIGHTH).log2_max;
+            self.record(run, Stage::FreshEncryption, noise, fresh);
+            let noise = compute_noise_lwe(&self.lwe_sk, &ct2, pt2, EIGHTH).log2_max;
+            self.record(run, Stage::FreshEncryption, noise, fresh);
+
+            // (0, 1/8) - ct1 - ct2, as in `gates::nand`
+            let mut linear_ct = LWECiphertext::allocate(ct1.lwe_size());
+            linear_ct.update_with_sub(ct1);
+            linear_ct.update_with_sub(ct2);
+            let body = linear_ct.get_body().0.wrapping_add(EIGHTH);
+            linear_ct.get_mut_body().0 = body;
+            let linear_pt = EIGHTH.wrapping_sub(pt1).wrapping_sub(pt2);
+            let noise = compute_noise_lwe(&self.lwe_sk, &linear_ct, linear_pt, EIGHTH).log2_max;
+            self.record(run, Stage::LinearCombination, noise, linear);
+
+            let noise = compute_noise_lwe(&self.lwe_sk, &linear_ct.modulus_switch(log_2n)?, linear_pt, EIGHTH).log2_max;
+            self.record(run, Stage::ModulusSwitch, noise, switched);
+
+            let mut accumulator = RLWECiphertext::allocate(self.server_key.polynomial_size());
+            accumulator.get_mut_body().as_mut_tensor().fill_with_element(EIGHTH);
+            let rotated_ct = self.server_key.bootstrap(&linear_ct, &mut accumulator)?;
+            let noise = self.gate_rlwe_noise(&rotated_ct);
+            self.record(run, Stage::BlindRotation, noise, rotated);
+
+            let mut extracted = LWECiphertext::allocate(LweSize(self.poly_size + 1));
+            constant_sample_extract(&mut extracted, &rotated_ct);
+            let noise = compute_noise_lwe(&self.extracted_sk, &extracted, expected, EIGHTH).log2_max;
+            self.record(run, Stage::SampleExtraction, noise, rotated);
+
+            let mut output = LWECiphertext::allocate(self.server_key.lwe_size());
+            self.server_key.keyswitch(&mut output, &extracted);
+            let noise = compute_noise_lwe(&self.lwe_sk, &output, expected, EIGHTH).log2_max;
+            self.record(run, Stage::Keyswitch, noise, keyswitched);
+            self.runs += 1;
+        }
+        Ok(())
+    }
+
+    /// The records of all the runs so far, 7 per run (the two fresh encryptions are recorded).
+    pub fn records(&self) -> &[NoiseRecord] {
+        &self.records
+    }
+
+    /// Write a header and one row per record, with the parameters the runs were made with.
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> csv::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(&["timestamp", "label", "k", "lwe_dimension", "poly_size", "run", "stage", "log2_noise", "predicted_log2_std"])?;
+        for record in self.records.iter() {
+            writer.write_record(&[
+                record.timestamp.to_rfc3339(),
+                self.label.clone(),
+                self.k.to_string(),
+                self.lwe_dimension.to_string(),
+                self.poly_size.to_string(),
+                record.run.to_string(),
+                record.stage.name().to_string(),
+                format!("{:.4}", record.log2_noise),
+                format!("{:.4}", record.predicted_log2_std),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn record(&mut self, run: usize, stage: Stage, log2_noise: f64, predicted_variance: f64) {
+        self.records.push(NoiseRecord {
+            timestamp: Local::now(),
+            run,
+            stage,
+            log2_noise,
+            predicted_log2_std: predicted_variance.log2() / 2. + Scalar::BITS as f64,
+        });
+    }
+
+    /// log2 of the largest distance of a coefficient of the phase of ct to the closest gate
+    /// encoding (+-1/8): the rotated test polynomial has both signs.
+    fn gate_rlwe_noise(&self, ct: &RLWECiphertext) -> f64 {
+        let mut pt = PlaintextList::allocate(Scalar::zero(), PlaintextCount(self.poly_size));
+        self.rlwe_sk.decrypt_wrapping_rlwe(&mut pt, ct);
+        let errors = pt.as_tensor().iter().map(|x| {
+            let to_plus = x.wrapping_sub(EIGHTH);
+            let to_minus = x.wrapping_add(EIGHTH);
+            if (to_plus as SignedScalar).unsigned_abs() < (to_minus as SignedScalar).unsigned_abs() { to_plus } else { to_minus }
+        });
+        NoiseStats::from_errors(errors, EIGHTH).log2_max
+    }
+}
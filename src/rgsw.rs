@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::mem::size_of;
 
 use concrete_commons::parameters::{CiphertextCount, DecompositionBaseLog, DecompositionLevelCount, GlweSize, MonomialDegree, PolynomialSize};
 use concrete_core::backends::core::private as ccore;
@@ -7,25 +8,35 @@ use concrete_core::backends::core::private::crypto::bootstrap::{FourierBuffers};
 use concrete_core::backends::core::private::crypto::ggsw::FourierGgswCiphertext;
 use concrete_core::backends::core::private::crypto::glwe::FourierGlweCiphertext;
 use concrete_core::backends::core::private::math::fft::{Complex64, AlignedVec, FourierPolynomial};
+use ccore::math::torus::UnsignedTorus;
 use get_size::GetSize;
 use num_traits::identities::Zero;
 use crate::rlwe::{RLWECiphertext};
 use crate::*;
+use crate::noise::NoiseStats;
+use crate::error::MfheError;
+use crate::params::ContextError;
 
 #[cfg(feature = "serde_serialize")]
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An RGSW ciphertext.
+/// An RGSW ciphertext over the torus of T, 64 bits by default.
 /// It is a wrapper around `StandardGgswCiphertext` from concrete.
-pub struct RGSWCiphertext(pub(crate) StandardGgswCiphertext<Vec<Scalar>>);
+pub struct RGSWCiphertext<T = Scalar>(pub(crate) StandardGgswCiphertext<Vec<T>>);
 
-impl RGSWCiphertext {
-    pub fn allocate(poly_size: PolynomialSize, decomp_base_log: DecompositionBaseLog, decomp_level: DecompositionLevelCount) -> RGSWCiphertext {
+impl<T: UnsignedTorus> GetSize for RGSWCiphertext<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> RGSWCiphertext<T> {
+    pub fn allocate(poly_size: PolynomialSize, decomp_base_log: DecompositionBaseLog, decomp_level: DecompositionLevelCount) -> RGSWCiphertext<T> {
         RGSWCiphertext(
             StandardGgswCiphertext::allocate(
-                Scalar::zero(),
+                T::ZERO,
                 poly_size,
                 GlweSize(2),
                 decomp_level,
@@ -54,8 +65,21 @@ impl RGSWCiphertext {
         self.0.as_tensor().get_size()
     }
 
-    pub(crate) fn external_product_with_buf_glwe<C>(&self, out: &mut GlweCiphertext<C>, d: &RLWECiphertext, buffers: &mut FourierBuffers<Scalar>)
-        where C: AsMutSlice<Element=Scalar>
+    pub fn get_last_row(&self) -> RLWECiphertext<T> {
+        self.get_nth_row(self.decomposition_level_count().0 * 2 - 1)
+    }
+
+    pub fn get_nth_row(&self, n: usize) -> RLWECiphertext<T> {
+        let mut glwe_ct = GlweCiphertext::allocate(T::ZERO, self.polynomial_size(), GlweSize(2));
+        glwe_ct.as_mut_tensor().fill_with_copy(self.0.as_glwe_list().ciphertext_iter().nth(n).unwrap().as_tensor());
+        RLWECiphertext(glwe_ct)
+    }
+}
+
+impl<T: UnsignedTorus> RGSWCiphertext<T> {
+
+    pub(crate) fn external_product_with_buf_glwe<C>(&self, out: &mut GlweCiphertext<C>, d: &RLWECiphertext<T>, buffers: &mut FourierBuffers<T>)
+        where C: AsMutSlice<Element=T>
     {
         let mut transformed = FourierGgswCiphertext::allocate(
             Complex64::new(0., 0.),
@@ -65,40 +89,77 @@ impl RGSWCiphertext {
             self.decomposition_base_log(),
         );
         transformed.fill_with_forward_fourier(&self.0, buffers);
-
-        let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
-
-        transformed.external_product(out, &d.0, &mut buffers);
+        transformed.external_product(out, &d.0, buffers);
     }
 
-    pub fn external_product_with_buf(&self, out: &mut RLWECiphertext, d: &RLWECiphertext, buffers: &mut FourierBuffers<Scalar>) {
+    pub fn external_product_with_buf(&self, out: &mut RLWECiphertext<T>, d: &RLWECiphertext<T>, buffers: &mut FourierBuffers<T>) {
         self.external_product_with_buf_glwe(&mut out.0, d, buffers);
     }
 
-    pub fn external_product(&self, out: &mut RLWECiphertext, d: &RLWECiphertext) {
+    pub fn external_product(&self, out: &mut RLWECiphertext<T>, d: &RLWECiphertext<T>) {
         let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
         self.external_product_with_buf(out, d, &mut buffers);
     }
+
+    ///CMux: output ct0 if self encrypts 0 and ct1 if self encrypts 1, i.e. ct0 + self ⊡ (ct1 - ct0).
+    pub fn cmux(&self, ct0: &RLWECiphertext<T>, ct1: &RLWECiphertext<T>) -> RLWECiphertext<T> {
+        let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
+        self.cmux_with_buf(ct0, ct1, &mut buffers)
+    }
+
+    ///Same as `cmux` reusing the buffers used for FFT.
+    pub fn cmux_with_buf(&self, ct0: &RLWECiphertext<T>, ct1: &RLWECiphertext<T>, buffers: &mut FourierBuffers<T>) -> RLWECiphertext<T> {
+        let mut diff = ct1.clone();
+        diff.update_with_sub(ct0);
+        let mut out = RLWECiphertext::allocate(self.polynomial_size());
+        self.external_product_with_buf(&mut out, &diff, buffers);
+        out.update_with_add(ct0);
+        out
+    }
     
-    pub fn internal_product(&self, out: &mut RGSWCiphertext, ctxt: &RGSWCiphertext, ctx: &mut Context){
-        for element in 0 .. out.ciphertext_count().0 {
-            let mut aux = RLWECiphertext::allocate(ctx.poly_size);
-            let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
-            let rlwe = ctxt.get_nth_row(element);
-            self.external_product_with_buf(&mut aux, &rlwe, &mut buffers);
-            (out.0.as_mut_glwe_list().ciphertext_iter_mut().nth(element).unwrap()).as_mut_tensor().fill_with_copy(&aux.get_glwe_copy(ctx).as_tensor());
+    pub fn internal_product(&self, out: &mut RGSWCiphertext<T>, ctxt: &RGSWCiphertext<T>, ctx: &mut Context){
+        let mut buffers = ctx.gen_fourier_buffers();
+        self.internal_product_with_buf(out, ctxt, &mut buffers);
+    }
+
+    ///Same as `internal_product` reusing the buffers used for FFT. It does not need the context, so
+    ///several threads can compute internal products at once, each one with its own buffers.
+    pub fn internal_product_with_buf(&self, out: &mut RGSWCiphertext<T>, ctxt: &RGSWCiphertext<T>, buffers: &mut FourierBuffers<T>){
+        for (element, mut row) in out.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
+            let mut aux = RLWECiphertext::allocate(self.polynomial_size());
+            self.external_product_with_buf(&mut aux, &ctxt.get_nth_row(element), buffers);
+            row.as_mut_tensor().fill_with_copy(aux.as_tensor());
+        }
+    }
+
+    ///Fill self with the trivial encryption of encoded, i.e. zero masks, no noise and encoded times
+    ///the gadget matrix. It can be computed from public information only.
+    pub fn fill_with_trivial_encryption(&mut self, encoded: &PlaintextList<Vec<T>>) {
+        let base_log = self.decomposition_base_log().0;
+        let mut buf = PlaintextList::allocate(T::ZERO, encoded.count());
+        self.0.as_mut_tensor().fill_with_element(T::ZERO);
+        for (i, mut m) in self.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
+            let level = (i / 2) + 1;
+            let shift: usize = T::BITS - base_log * level;
+            buf.as_mut_tensor().fill_with_copy(encoded.as_tensor());
+            mul_const(&mut buf.as_mut_tensor(), T::ONE << shift);
+            if i % 2 == 0 {
+                m.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_add(&buf.as_polynomial());
+            } else {
+                m.get_mut_body().as_mut_polynomial().update_with_wrapping_add(&buf.as_polynomial());
+            }
         }
     }
 
     ///Multiplies out by X^degree. TODO: remove self
-    pub fn product_monomial(&self, out: &mut RGSWCiphertext, degree: MonomialDegree){
+    pub fn product_monomial(&self, out: &mut RGSWCiphertext<T>, degree: MonomialDegree){
         for element in 0 .. self.ciphertext_count().0 {
             (out.0.as_mut_glwe_list().ciphertext_iter_mut().nth(element).unwrap()).as_mut_polynomial_list().update_with_wrapping_monic_monomial_mul(degree);
         }
     }
 
     ///Substract ctxt from self
-    pub fn sub_ciphertext(&mut self, ctxt: &RGSWCiphertext) {
+    pub fn sub_ciphertext(&mut self, ctxt: &RGSWCiphertext<T>) {
         for (i, mut ct) in self.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
                 ct.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_sub(
                     &ctxt.0.as_glwe_list().ciphertext_iter().nth(i).unwrap().get_mask().as_polynomial_list().get_polynomial(0));
@@ -108,7 +169,7 @@ impl RGSWCiphertext {
     }
 
     ///Add ctxt to self
-    pub fn add_ciphertext(&mut self, ctxt: &RGSWCiphertext) {
+    pub fn add_ciphertext(&mut self, ctxt: &RGSWCiphertext<T>) {
         for (i, mut ct) in self.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
                 ct.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_add(
                     &ctxt.0.as_glwe_list().ciphertext_iter().nth(i).unwrap().get_mask().as_polynomial_list().get_polynomial(0));
@@ -117,54 +178,43 @@ impl RGSWCiphertext {
         }
     }
 
-
-    pub fn get_last_row(&self) -> RLWECiphertext {
-        self.get_nth_row(self.decomposition_level_count().0 * 2 - 1)
-    }
-
-    pub fn get_nth_row(&self, n: usize) -> RLWECiphertext {
-        let mut glwe_ct = GlweCiphertext::allocate(Scalar::zero(), self.polynomial_size(), GlweSize(2));
-        glwe_ct.as_mut_tensor().fill_with_copy(self.0.as_glwe_list().ciphertext_iter().nth(n).unwrap().as_tensor());
-        RLWECiphertext(glwe_ct)
-    }
-
-    pub fn get_last_row_nocast(&self) -> GlweCiphertext<Vec<Scalar>> {
+    pub fn get_last_row_nocast(&self) -> GlweCiphertext<Vec<T>> {
         return self.get_nth_row_nocast(self.decomposition_level_count().0 * 2 - 1)
     }
 
-    pub fn get_nth_row_nocast(&self, n: usize) -> GlweCiphertext<Vec<Scalar>> {
-        let mut glwe_ct = GlweCiphertext::allocate(Scalar::zero(), self.polynomial_size(), GlweSize(2));
+    pub fn get_nth_row_nocast(&self, n: usize) -> GlweCiphertext<Vec<T>> {
+        let mut glwe_ct = GlweCiphertext::allocate(T::ZERO, self.polynomial_size(), GlweSize(2));
         glwe_ct.as_mut_tensor().fill_with_copy(self.0.as_glwe_list().ciphertext_iter().nth(n).unwrap().as_tensor());
         return glwe_ct;
     }
 }
 
-pub fn compute_noise_rgsw(gsw_ct: &RGSWCiphertext , ptxt: &PlaintextList<Vec<Scalar>>, sk: &RLWESecretKey, ctx: &Context) -> f64 {
+/// Compute the statistics of the errors of the second row of gsw_ct, the one with the largest
+/// gadget factor on the body, where ptxt is its expected encoding (see `NoiseStats`).
+pub fn compute_noise_rgsw(gsw_ct: &RGSWCiphertext , ptxt: &PlaintextList<Vec<Scalar>>, sk: &RLWESecretKey, ctx: &Context, threshold: Scalar) -> NoiseStats {
     let mut error_pt = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
     sk.decrypt_wrapping_rlwe(&mut error_pt, &gsw_ct.get_nth_row(1));
 
     error_pt.as_mut_polynomial().update_with_wrapping_sub(&ptxt.as_polynomial());
 
-    let mut max_e = 0f64;
-    for x in error_pt.as_tensor().iter() {
-        // convert x to signed
-        let z = (*x as SignedScalar).abs() as f64;
-        //println!("z: {}",z);
-        if z > max_e {
-            max_e = z;
-        }
-    }
-    max_e.log2()
+    NoiseStats::from_errors(error_pt.as_tensor().iter().copied(), threshold)
 }
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An RGSW ciphertext.
-/// It is a wrapper around `StandardGgswCiphertext` from concrete.
-pub struct FourierRGSWCiphertext(pub(crate) FourierGgswCiphertext<AlignedVec<Complex64>, Scalar>);
+/// An RGSW ciphertext over the torus of T in the Fourier domain.
+/// It is a wrapper around `FourierGgswCiphertext` from concrete.
+pub struct FourierRGSWCiphertext<T = Scalar>(pub(crate) FourierGgswCiphertext<AlignedVec<Complex64>, T>);
+
+impl<T: UnsignedTorus> GetSize for FourierRGSWCiphertext<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<Complex64>()
+    }
+}
 
-impl FourierRGSWCiphertext {
+impl<T: UnsignedTorus> FourierRGSWCiphertext<T> {
 
-    pub fn allocate(poly_size: PolynomialSize, decomp_base_log: DecompositionBaseLog, decomp_level: DecompositionLevelCount) -> FourierRGSWCiphertext {
+    pub fn allocate(poly_size: PolynomialSize, decomp_base_log: DecompositionBaseLog, decomp_level: DecompositionLevelCount) -> FourierRGSWCiphertext<T> {
         FourierRGSWCiphertext(
             FourierGgswCiphertext::allocate(
                 Complex64::new(0., 0.),
@@ -189,7 +239,7 @@ impl FourierRGSWCiphertext {
     }
 
     ///Add ctxt to self
-    pub fn add_ciphertext(&mut self, ctxt: &FourierRGSWCiphertext) {
+    pub fn add_ciphertext(&mut self, ctxt: &FourierRGSWCiphertext<T>) {
         for (i, mut ct) in self.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
             let (mut body, mut mask) = ct.get_mut_body_and_mask();
             //let now = Instant::now();
@@ -199,21 +249,21 @@ impl FourierRGSWCiphertext {
     }
 
     ///Substract ctxt from self
-    pub fn sub_ciphertext(&mut self, ctxt: &FourierRGSWCiphertext) {
+    pub fn sub_ciphertext(&mut self, ctxt: &FourierRGSWCiphertext<T>) {
         for (i, mut ct) in self.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
             let (mut body, mut mask) = ct.get_mut_body_and_mask();
-            mask.as_mut_polynomial_list().get_mut_polynomial(0).as_mut_tensor().update_with_add(ctxt.0.as_glwe_list().ciphertext_iter().nth(i).unwrap().get_mask().as_polynomial_list().get_polynomial(0).as_tensor());
+            mask.as_mut_polynomial_list().get_mut_polynomial(0).as_mut_tensor().update_with_sub(ctxt.0.as_glwe_list().ciphertext_iter().nth(i).unwrap().get_mask().as_polynomial_list().get_polynomial(0).as_tensor());
             body.as_mut_tensor().update_with_sub(&ctxt.0.as_glwe_list().ciphertext_iter().nth(i).unwrap().get_body().as_tensor());
         }
     }
 
     
 
-    pub fn fill_with_forward_fourier(&mut self, ctxt: &RGSWCiphertext, buffers: &mut FourierBuffers<Scalar>) {
+    pub fn fill_with_forward_fourier(&mut self, ctxt: &RGSWCiphertext<T>, buffers: &mut FourierBuffers<T>) {
         self.0.fill_with_forward_fourier(&ctxt.0, buffers);
     }
 
-    pub fn get_nth_row(&self, n: usize) -> FourierRLWECiphertext {
+    pub fn get_nth_row(&self, n: usize) -> FourierRLWECiphertext<T> {
         let mut glwe_ct = FourierGlweCiphertext::allocate(Complex64::new(0., 0.), self.polynomial_size(), GlweSize(2));
         glwe_ct.as_mut_tensor().fill_with_copy(self.0.as_glwe_list().ciphertext_iter().nth(n).unwrap().as_tensor());
         FourierRLWECiphertext(glwe_ct)
@@ -221,34 +271,168 @@ impl FourierRGSWCiphertext {
 
     }
     
-    ///Multiply self by monomial, placing the result in out
-    pub fn product_monomial(&self, out: &mut FourierRGSWCiphertext, monomial: &FourierPolynomial<AlignedVec<Complex64>>){
-        
-        for element in 0 .. self.ciphertext_count().0 {
-            for (_i, mut poly) in (out.0.as_mut_glwe_list().ciphertext_iter_mut().nth(element).unwrap()).as_mut_polynomial_list().polynomial_iter_mut().enumerate() {
-                let mut p = FourierPolynomial::allocate(Complex64::new(0., 0.), poly.polynomial_size());
-                
-                for (i,coeff) in p.coefficient_iter_mut().enumerate() {
-                    *coeff = *poly.get_monomial(MonomialDegree(i)).get_coefficient();
-                }
+    ///Multiply self by monomial, placing the result in out.
+    ///In the Fourier domain this is a coefficient-wise product of every polynomial with monomial.
+    pub fn product_monomial(&self, out: &mut FourierRGSWCiphertext<T>, monomial: &FourierPolynomial<AlignedVec<Complex64>>){
+        let poly_size = self.polynomial_size().0;
+        for (mut out_poly, poly) in out.0.as_mut_tensor().subtensor_iter_mut(poly_size)
+            .zip(self.0.as_tensor().subtensor_iter(poly_size)) {
+            out_poly.update_with_two(&poly, monomial.as_tensor(), |o, p, m| *o = *p * *m);
+        }
+    }
+
+    pub fn external_product_with_buf(&self, out: &mut RLWECiphertext<T>, d: &RLWECiphertext<T>, buffers: &mut FourierBuffers<T>) {
+        self.0.external_product(&mut out.0, &d.0, buffers);
+    }
+
+    pub fn external_product(&self, out: &mut RLWECiphertext<T>, d: &RLWECiphertext<T>) {
+        let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
+        self.0.external_product(&mut out.0, &d.0, &mut buffers);
+    }
+
+    ///Same as `RGSWCiphertext::cmux_with_buf`, without the forward FFT of self.
+    pub fn cmux_with_buf(&self, ct0: &RLWECiphertext<T>, ct1: &RLWECiphertext<T>, buffers: &mut FourierBuffers<T>) -> RLWECiphertext<T> {
+        let mut diff = ct1.clone();
+        diff.update_with_sub(ct0);
+        let mut out = RLWECiphertext::allocate(self.polynomial_size());
+        self.external_product_with_buf(&mut out, &diff, buffers);
+        out.update_with_add(ct0);
+        out
+    }
+
+}
+
+
+/// The multiparty bootstrapping key in the Fourier domain.
+/// For every LWE coordinate it holds the list [RGSW(ind_0), ..., RGSW(ind_k)] output by
+/// `homomorphic_indicator`, converted once with the forward FFT, together with the Fourier
+/// transform of the monomials X^d for d in [0, 2N).
+/// `from_fourier` checks that there is at least one coordinate and one party, so the accessors
+/// below can read the parameters from the first ciphertext.
+/// Only the indicator lists are encoded, the monomials are computed again when the key is loaded.
+pub struct FourierBootstrappingKey<T = Scalar> {
+    pub(crate) keys: Vec<Vec<FourierRGSWCiphertext<T>>>,
+    pub(crate) monomials: Vec<FourierPolynomial<AlignedVec<Complex64>>>,
+}
 
-                let mut res = FourierPolynomial::allocate(Complex64::new(0., 0.), poly.polynomial_size());
-                res.update_with_multiply_accumulate(&p, &monomial);
+impl<T: UnsignedTorus> GetSize for FourierBootstrappingKey<T> {
+    fn get_heap_size(&self) -> usize {
+        let keys: usize = self.keys.iter()
+            .map(|list| GetSize::get_heap_size(list))
+            .sum();
+        let monomials: usize = self.monomials.iter()
+            .map(|poly| poly.as_tensor().len() * size_of::<Complex64>())
+            .sum();
+        keys + monomials
+            + self.keys.capacity() * size_of::<Vec<FourierRGSWCiphertext<T>>>()
+            + self.monomials.capacity() * size_of::<FourierPolynomial<AlignedVec<Complex64>>>()
+    }
+}
+
+impl<T: UnsignedTorus> FourierBootstrappingKey<T> {
+    /// Every LWE coordinate must have an indicator list of the same length k + 1, with k >= 1.
+    pub fn from_standard(bootstrappingkeys: &[Vec<RGSWCiphertext<T>>], buffers: &mut FourierBuffers<T>) -> Result<FourierBootstrappingKey<T>, MfheError> {
+        let keys = bootstrappingkeys.iter().map(|list| {
+            list.iter().map(|ct| {
+                let mut fourier_ct = FourierRGSWCiphertext::allocate(ct.polynomial_size(), ct.decomposition_base_log(), ct.decomposition_level_count());
+                fourier_ct.fill_with_forward_fourier(ct, buffers);
+                fourier_ct
+            }).collect()
+        }).collect();
+        FourierBootstrappingKey::from_fourier(keys)
+    }
 
-                for (i,coeff) in poly.coefficient_iter_mut().enumerate() {
-                    *coeff = *res.coefficient_iter().nth(i).unwrap();
+    /// Same as `from_standard` with the indicator lists already in the Fourier domain, e.g. the
+    /// ones of a loaded key. Every ciphertext must have the parameters of the first one.
+    pub fn from_fourier(keys: Vec<Vec<FourierRGSWCiphertext<T>>>) -> Result<FourierBootstrappingKey<T>, MfheError> {
+        let first_list = keys.first().ok_or(ContextError::EmptyLweDimension)?;
+        if first_list.len() < 2 {
+            return Err(ContextError::NoParties.into());
+        }
+        let first = &first_list[0].0;
+        let (poly_size, base_log, level_count) = (first.polynomial_size(), first.decomposition_base_log(), first.decomposition_level_count());
+        if base_log.0 == 0 || level_count.0 == 0 || base_log.0 * level_count.0 > T::BITS {
+            return Err(MfheError::ParameterMismatch("bootstrapping key decomposition"));
+        }
+        let tensor_len = level_count.0 * 2 * 2 * poly_size.0;
+        for list in keys.iter() {
+            if list.len() != first_list.len() {
+                return Err(MfheError::DimensionMismatch { what: "indicator list", expected: first_list.len(), found: list.len() });
+            }
+            for ct in list.iter().map(|ct| &ct.0) {
+                if ct.polynomial_size() != poly_size {
+                    return Err(MfheError::DimensionMismatch { what: "bootstrapping key polynomial", expected: poly_size.0, found: ct.polynomial_size().0 });
+                }
+                if ct.glwe_size() != GlweSize(2) || ct.decomposition_base_log() != base_log || ct.decomposition_level_count() != level_count {
+                    return Err(MfheError::ParameterMismatch("bootstrapping key ciphertext"));
+                }
+                if ct.as_tensor().len() != tensor_len {
+                    return Err(MfheError::DimensionMismatch { what: "bootstrapping key ciphertext", expected: tensor_len, found: ct.as_tensor().len() });
                 }
-                
-                
-                
             }
         }
 
+        let buffers = FourierBuffers::<T>::new(poly_size, GlweSize(2));
+        // X^d for d >= N is -X^(d-N) in Z[X]/(X^N + 1).
+        let monomials = (0..2 * poly_size.0).map(|d| {
+            let mut poly = Polynomial::allocate(T::ZERO, poly_size);
+            if d < poly_size.0 {
+                *poly.get_mut_monomial(MonomialDegree(d)).get_mut_coefficient() = T::ONE;
+            } else {
+                *poly.get_mut_monomial(MonomialDegree(d - poly_size.0)).get_mut_coefficient() = T::MAX;
+            }
+            let mut fourier_poly = FourierPolynomial::allocate(Complex64::new(0., 0.), poly_size);
+            buffers.fft_buffers.fft.forward_as_integer(&mut fourier_poly, &poly);
+            fourier_poly
+        }).collect();
+
+        Ok(FourierBootstrappingKey { keys, monomials })
     }
 
-    pub fn external_product(&self, out: &mut RLWECiphertext, d: &RLWECiphertext) {
-        let mut buffers = FourierBuffers::new(self.polynomial_size(), GlweSize(2));
-        self.0.external_product(&mut out.0, &d.0, &mut buffers);
+    pub fn polynomial_size(&self) -> PolynomialSize {
+        self.keys[0][0].polynomial_size()
+    }
+
+    pub fn decomposition_base_log(&self) -> DecompositionBaseLog {
+        self.keys[0][0].decomposition_base_log()
+    }
+
+    pub fn decomposition_level_count(&self) -> DecompositionLevelCount {
+        self.keys[0][0].0.decomposition_level_count()
+    }
+
+    /// Return the number of LWE coordinates n covered by the key.
+    pub fn lwe_dimension(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Return the number of parties k, the indicator lists hold k+1 ciphertexts.
+    pub fn party_count(&self) -> usize {
+        self.keys[0].len() - 1
     }
 
+    /// Return the indicator list of the index-th LWE coordinate.
+    pub fn indicators(&self, index: usize) -> &[FourierRGSWCiphertext<T>] {
+        &self.keys[index]
+    }
+
+    /// Return the Fourier transform of X^degree, the degree is taken modulo 2N.
+    pub fn monomial(&self, degree: MonomialDegree) -> &FourierPolynomial<AlignedVec<Complex64>> {
+        &self.monomials[degree.0 % (2 * self.polynomial_size().0)]
+    }
+}
+
+#[cfg(feature = "serde_serialize")]
+impl<T: UnsignedTorus> Serialize for FourierBootstrappingKey<T> where FourierRGSWCiphertext<T>: Serialize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.keys.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde_serialize")]
+impl<'de, T: UnsignedTorus> Deserialize<'de> for FourierBootstrappingKey<T> where FourierRGSWCiphertext<T>: Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys = Vec::<Vec<FourierRGSWCiphertext<T>>>::deserialize(deserializer)?;
+        FourierBootstrappingKey::from_fourier(keys).map_err(de::Error::custom)
+    }
 }
@@ -13,95 +13,107 @@ use concrete_core::backends::core::private::crypto::glwe::FourierGlweCiphertext;
 use concrete_core::backends::core::private::crypto::secret::LweSecretKey;
 use concrete_core::backends::core::private::math::fft::{AlignedVec, Complex64};
 use concrete_core::backends::core::private::math::torus::UnsignedTorus;
-use num_traits::identities::{One, Zero};
+use num_traits::identities::Zero;
 use crate::*;
+use crate::error::MfheError;
+use crate::noise::NoiseStats;
+use std::mem::size_of;
+use get_size::GetSize;
 
 #[cfg(feature = "serde_serialize")]
 use serde::{Deserialize, Serialize};
 
 #[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An RLWE ciphertext.
+/// An RLWE ciphertext over the torus of T, 64 bits by default.
 /// It is a wrapper around `GlweCiphertext` from concrete.
-pub struct RLWECiphertext(pub(crate) GlweCiphertext<Vec<Scalar>>);
+pub struct RLWECiphertext<T = Scalar>(pub(crate) GlweCiphertext<Vec<T>>);
 
-impl RLWECiphertext {
-    pub fn allocate(poly_size: PolynomialSize) -> RLWECiphertext {
-        RLWECiphertext(GlweCiphertext::from_container(vec![Scalar::zero(); poly_size.0 * 2], poly_size))
+impl<T: UnsignedTorus> GetSize for RLWECiphertext<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
     }
+}
 
+impl RLWECiphertext {
     pub fn get_glwe_copy(&self, ctx: &mut Context) -> GlweCiphertext<Vec<Scalar>> {
         let mut glwe = GlweCiphertext::allocate(0 as Scalar, ctx.poly_size, GlweSize(100));
         glwe.clone_from(&self.0);
         return glwe;
     }
+}
+
+impl<T: UnsignedTorus> RLWECiphertext<T> {
+    pub fn allocate(poly_size: PolynomialSize) -> RLWECiphertext<T> {
+        RLWECiphertext(GlweCiphertext::from_container(vec![T::ZERO; poly_size.0 * 2], poly_size))
+    }
 
     pub fn polynomial_size(&self) -> PolynomialSize {
         self.0.polynomial_size()
     }
 
-    pub fn get_body(&self) -> GlweBody<&[Scalar]> {
+    pub fn get_body(&self) -> GlweBody<&[T]> {
         self.0.get_body()
     }
 
-    pub fn get_mask(&self) -> GlweMask<&[Scalar]> {
+    pub fn get_mask(&self) -> GlweMask<&[T]> {
         self.0.get_mask()
     }
 
-    pub fn get_mut_mask(&mut self) -> GlweMask<&mut [Scalar]> {
+    pub fn get_mut_mask(&mut self) -> GlweMask<&mut [T]> {
         self.0.get_mut_mask()
     }
 
-    pub fn get_mut_body(&mut self) -> GlweBody<&mut [Scalar]> {
+    pub fn get_mut_body(&mut self) -> GlweBody<&mut [T]> {
         self.0.get_mut_body()
     }
 
-    pub fn as_tensor(& self) -> &Tensor<Vec<Scalar>>{
+    pub fn as_tensor(& self) -> &Tensor<Vec<T>>{
         self.0.as_tensor()
     }
 
     pub fn clear(&mut self) {
-        self.0.as_mut_tensor().fill_with(|| Scalar::zero());
+        self.0.as_mut_tensor().fill_with(|| T::ZERO);
     }
 
-    pub fn fill_with_copy(&mut self, other: &RLWECiphertext) {
+    pub fn fill_with_copy(&mut self, other: &RLWECiphertext<T>) {
         self.0.as_mut_tensor().fill_with_copy(other.0.as_tensor());
     }
 
     pub fn update_mask_with_add<C>(&mut self, other: &Polynomial<C>)
-        where C: AsRefSlice<Element=Scalar>
+        where C: AsRefSlice<Element=T>
     {
         self.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0)
             .update_with_wrapping_add(other);
     }
 
     pub fn update_mask_with_sub<C>(&mut self, other: &Polynomial<C>)
-        where C: AsRefSlice<Element=Scalar>
+        where C: AsRefSlice<Element=T>
     {
         self.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0)
             .update_with_wrapping_sub(other);
     }
 
     pub fn update_body_with_add<C>(&mut self, other: &Polynomial<C>)
-        where C: AsRefSlice<Element=Scalar>
+        where C: AsRefSlice<Element=T>
     {
         self.get_mut_body().as_mut_polynomial()
             .update_with_wrapping_add(other);
     }
 
     pub fn update_body_with_sub<C>(&mut self, other: &Polynomial<C>)
-        where C: AsRefSlice<Element=Scalar>
+        where C: AsRefSlice<Element=T>
     {
         self.get_mut_body().as_mut_polynomial()
             .update_with_wrapping_sub(other);
     }
 
-    pub fn update_with_add(&mut self, other: &RLWECiphertext) {
+    pub fn update_with_add(&mut self, other: &RLWECiphertext<T>) {
         self.update_mask_with_add(&other.get_mask().as_polynomial_list().get_polynomial(0));
         self.update_body_with_add(&other.get_body().as_polynomial());
     }
 
-    pub fn update_with_sub(&mut self, other: &RLWECiphertext) {
+    pub fn update_with_sub(&mut self, other: &RLWECiphertext<T>) {
         self.update_mask_with_sub(&other.get_mask().as_polynomial_list().get_polynomial(0));
         self.update_body_with_sub(&other.get_body().as_polynomial());
     }
@@ -110,19 +122,32 @@ impl RLWECiphertext {
         self.get_mut_body().as_mut_polynomial().update_with_wrapping_unit_monomial_div(m);
         self.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_unit_monomial_div(m);
     }
+
+    pub fn update_with_monomial_mul(&mut self, m: MonomialDegree) {
+        self.get_mut_body().as_mut_polynomial().update_with_wrapping_monic_monomial_mul(m);
+        self.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_monic_monomial_mul(m);
+    }
 }
 
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An RLWE ciphertext.
+/// An RLWE public key over the torus of T: m RLWE encryptions of zero.
 /// It is a wrapper around `GlweCiphertext` from concrete.
-pub struct RLWEPublicKey(pub(crate) Vec<GlweCiphertext<Vec<Scalar>>>);
+pub struct RLWEPublicKey<T = Scalar>(pub(crate) Vec<GlweCiphertext<Vec<T>>>);
+
+impl<T: UnsignedTorus> GetSize for RLWEPublicKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.iter().map(|ct| ct.as_tensor().len() * size_of::<T>()).sum::<usize>()
+            + self.0.capacity() * size_of::<GlweCiphertext<Vec<T>>>()
+    }
+}
 
-impl RLWEPublicKey {
-    pub fn allocate(poly_size: PolynomialSize, glwe_size: GlweSize, m: usize) -> RLWEPublicKey {
-        let mut glwe_list: Vec<GlweCiphertext<Vec<Scalar>>> = Vec::new();
+impl<T: UnsignedTorus> RLWEPublicKey<T> {
+    pub fn allocate(poly_size: PolynomialSize, glwe_size: GlweSize, m: usize) -> RLWEPublicKey<T> {
+        let mut glwe_list: Vec<GlweCiphertext<Vec<T>>> = Vec::new();
         for _i in 0..m {
-            let ct = GlweCiphertext::from_container(vec![Scalar::zero(); poly_size.0 * glwe_size.0], poly_size);
+            let ct = GlweCiphertext::from_container(vec![T::ZERO; poly_size.0 * glwe_size.0], poly_size);
             glwe_list.push(ct);
         }
         RLWEPublicKey(glwe_list)
@@ -132,44 +157,94 @@ impl RLWEPublicKey {
         self.0.len()
     }
 
-    pub fn update_with_global_mask(&mut self, global_pk: &RLWEPublicKey, ctx: &Context) {
+    /// Fill the masks of self from the mask stream of a generator seeded with seed.
+    pub(crate) fn fill_mask_from_seed(&mut self, seed: u128) {
+        let poly_size = self.0.first().map_or(PolynomialSize(1), |ct| ct.polynomial_size());
+        let mut generator = EncryptionRandomGenerator::new(Some(seed));
+        RLWESecretKey::<T>::zero(poly_size).generate_mask(self, &mut generator);
+    }
+
+    /// Keep only the bodies of self. The masks must have been expanded from seed (see
+    /// `CommonReferenceString`), they are regenerated by `CompressedRLWEPublicKey::decompress`.
+    pub fn compress(&self, seed: u128) -> CompressedRLWEPublicKey<T> {
+        let poly_size = self.0.first().map_or(PolynomialSize(1), |ct| ct.polynomial_size());
+        debug_assert!({
+            let mut expanded = RLWEPublicKey::<T>::allocate(poly_size, GlweSize(2), self.get_size());
+            expanded.fill_mask_from_seed(seed);
+            expanded.compare_mask(self, self.get_size())
+        }, "the masks of the public key are not expanded from the seed");
+        let mut bodies = Vec::with_capacity(poly_size.0 * self.get_size());
+        for ct in self.0.iter() {
+            bodies.extend_from_slice(ct.get_body().as_tensor().as_slice());
+        }
+        CompressedRLWEPublicKey { seed, poly_size: poly_size.0, bodies }
+    }
+
+    pub fn update_with_global_mask(&mut self, global_pk: &RLWEPublicKey<T>, ctx: &Context) {
         for i in 0..ctx.m {
             self.0.get_mut(i).unwrap().get_mut_mask().as_mut_tensor().fill_with_copy(global_pk.0.get(i).unwrap().get_mask().as_tensor());
         }
     }
 
-    pub fn update_body_with_pk(&mut self, pk: &RLWEPublicKey, ctx: &Context) {
+    pub fn update_body_with_pk(&mut self, pk: &RLWEPublicKey<T>, ctx: &Context) {
         for i in 0..ctx.m {
             self.0.get_mut(i).unwrap().get_mut_body().as_mut_tensor().update_with_wrapping_add(pk.0.get(i).unwrap().get_body().as_tensor());
         }
     }
 
-    pub fn encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>, generator: &mut SecretRandomGenerator, m: usize) {
+    /// Encrypt pt with a random subset sum of the m samples of the public key.
+    pub fn encrypt_rlwe(&self, encrypted: &mut RLWECiphertext<T>, pt: &PlaintextList<Vec<T>>, generator: &mut SecretRandomGenerator, m: usize) -> Result<(), MfheError> {
 
-        assert_eq!(m,self.get_size());
+        if m != self.get_size() {
+            return Err(MfheError::DimensionMismatch { what: "RLWE public key", expected: m, found: self.get_size() });
+        }
 
-        if m == Scalar::one() as usize{
+        if m == 1 {
             encrypted.0.get_mut_mask().as_mut_tensor().update_with_wrapping_add(self.0.get(0).unwrap().get_mask().as_tensor());
             encrypted.0.get_mut_body().as_mut_polynomial().update_with_wrapping_add(&self.0.get(0).unwrap().get_body().as_polynomial());
         } else {
-            let r = RLWESecretKey::generate_binary(PolynomialSize(m), generator);
+            let r = RLWESecretKey::<T>::generate_binary(PolynomialSize(m), generator);
             //println!("Random vector = {:?}", r.as_tensor());
         
             for (i, val) in r.0.as_polynomial_list().as_tensor().iter().enumerate() {
-                if *val == Scalar::one() {
+                if *val == T::ONE {
                     encrypted.0.get_mut_mask().as_mut_tensor().update_with_wrapping_add(self.0.get(i).unwrap().get_mask().as_tensor());
                     encrypted.0.get_mut_body().as_mut_polynomial().update_with_wrapping_add(&self.0.get(i).unwrap().get_body().as_polynomial());
                 }
             }
         }
         encrypted.0.get_mut_body().as_mut_polynomial().update_with_wrapping_add(&pt.as_polynomial());
+        Ok(())
+    }
+
+    /// Encrypt encoded as a RGSW ciphertext with the public key. Every row is a fresh public key
+    /// encryption of zero to which encoded times the gadget matrix is added: on the mask for the
+    /// even rows and on the body for the odd rows, as in `RLWESecretKey::encrypt_rgsw`. No secret
+    /// key is needed and the result decrypts under the secret key of the public key.
+    pub fn encrypt_rgsw(&self, out: &mut RGSWCiphertext<T>, encoded: &PlaintextList<Vec<T>>, ctx: &mut Context) -> Result<(), MfheError> {
+        let zero = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
+        out.fill_with_trivial_encryption(encoded);
+        for mut row in out.0.as_mut_glwe_list().ciphertext_iter_mut() {
+            let mut ct = RLWECiphertext::allocate(ctx.poly_size);
+            self.encrypt_rlwe(&mut ct, &zero, &mut ctx.secret_generator, ctx.m)?;
+            row.as_mut_tensor().update_with_wrapping_add(ct.as_tensor());
+        }
+        Ok(())
+    }
 
+    /// Encrypt a scalar as a RGSW ciphertext with the public key.
+    pub fn encrypt_constant_rgsw(&self, out: &mut RGSWCiphertext<T>, pt: &Plaintext<T>, ctx: &mut Context) -> Result<(), MfheError> {
+        let mut encoded = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
+        *encoded.as_mut_polynomial().get_mut_monomial(MonomialDegree(0)).get_mut_coefficient() = pt.0;
+        self.encrypt_rgsw(out, &encoded, ctx)
     }
 
-    pub fn compare_mask(&self, pk: &RLWEPublicKey, m: usize) {
-        for i in 0..m {
-            assert_eq!(self.0.get(i).unwrap().get_mask().as_polynomial_list().as_tensor(),pk.0.get(i).unwrap().get_mask().as_polynomial_list().as_tensor());
-        }
+    /// Return true if the first m samples of self and pk hold the same masks.
+    pub fn compare_mask(&self, pk: &RLWEPublicKey<T>, m: usize) -> bool {
+        (0..m).all(|i| match (self.0.get(i), pk.0.get(i)) {
+            (Some(a), Some(b)) => a.get_mask().as_polynomial_list().as_tensor() == b.get_mask().as_polynomial_list().as_tensor(),
+            _ => false,
+        })
     }
 
     pub fn get_numberof_mask(&self) -> PolynomialCount {
@@ -179,11 +254,84 @@ impl RLWEPublicKey {
 }
 
 
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone)]
-/// An RLWE secret key.
-pub struct RLWESecretKey(pub(crate) GlweSecretKey<BinaryKeyKind, Vec<Scalar>>);
+/// An RLWE public key stored as the seed of its masks and its m bodies, half the size of
+/// `RLWEPublicKey`.
+pub struct CompressedRLWEPublicKey<T = Scalar> {
+    seed: u128,
+    poly_size: usize,
+    bodies: Vec<T>,
+}
 
-impl RLWESecretKey {
+impl<T: UnsignedTorus> GetSize for CompressedRLWEPublicKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.bodies.len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> CompressedRLWEPublicKey<T> {
+    pub fn seed(&self) -> u128 {
+        self.seed
+    }
+
+    /// Regenerate the masks from the seed. The sizes come from a message, they are checked against
+    /// the ones of ctx (N and m) before anything is allocated.
+    pub fn decompress(&self, ctx: &Context) -> Result<RLWEPublicKey<T>, MfheError> {
+        if self.poly_size != ctx.poly_size.0 {
+            return Err(MfheError::DimensionMismatch { what: "compressed RLWE public key", expected: ctx.poly_size.0, found: self.poly_size });
+        }
+        if self.bodies.len() != ctx.m * self.poly_size {
+            return Err(MfheError::DimensionMismatch { what: "list of RLWE public key bodies", expected: ctx.m * self.poly_size, found: self.bodies.len() });
+        }
+        let poly_size = PolynomialSize(self.poly_size);
+        let mut pk = RLWEPublicKey::allocate(poly_size, GlweSize(2), self.bodies.len() / self.poly_size);
+        pk.fill_mask_from_seed(self.seed);
+        for (ct, body) in pk.0.iter_mut().zip(self.bodies.chunks(self.poly_size)) {
+            ct.get_mut_body().as_mut_tensor().as_mut_slice().copy_from_slice(body);
+        }
+        Ok(pk)
+    }
+}
+
+
+#[derive(Debug, Clone)]
+/// The share of one party for the threshold decryption of a RLWE ciphertext, see
+/// `RLWESecretKey::partial_decrypt`.
+pub struct RLWEDecryptionShare<T = Scalar> {
+    pub party_id: usize,
+    pub(crate) value: Polynomial<Vec<T>>,
+}
+
+/// Recover the (noisy) plaintext of ct from the decryption shares of all the k parties:
+/// b - sum_i (a*s_i + e_i). There must be exactly one share per party id in [0, k), see
+/// `combine_shares`.
+pub fn combine_rlwe_shares<T: UnsignedTorus>(ct: &RLWECiphertext<T>, shares: &[RLWEDecryptionShare<T>], k: usize) -> Result<PlaintextList<Vec<T>>, MfheError> {
+    check_party_ids("list of RLWE decryption shares", shares.iter().map(|share| share.party_id), k)?;
+    let poly_size = ct.polynomial_size().0;
+    if let Some(share) = shares.iter().find(|share| share.value.polynomial_size().0 != poly_size) {
+        return Err(MfheError::DimensionMismatch { what: "RLWE decryption share", expected: poly_size, found: share.value.polynomial_size().0 });
+    }
+    let mut pt = PlaintextList::allocate(T::ZERO, PlaintextCount(ct.polynomial_size().0));
+    pt.as_mut_polynomial().update_with_wrapping_add(&ct.get_body().as_polynomial());
+    for share in shares {
+        pt.as_mut_polynomial().update_with_wrapping_sub(&share.value);
+    }
+    Ok(pt)
+}
+
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone)]
+/// An RLWE secret key over the torus of T, its coefficients are binary.
+pub struct RLWESecretKey<T = Scalar>(pub(crate) GlweSecretKey<BinaryKeyKind, Vec<T>>);
+
+impl<T: UnsignedTorus> GetSize for RLWESecretKey<T> {
+    fn get_heap_size(&self) -> usize {
+        self.0.as_tensor().len() * size_of::<T>()
+    }
+}
+
+impl<T: UnsignedTorus> RLWESecretKey<T> {
     /// Generate a secret key where the coefficients are binary.
     pub fn generate_binary(
         poly_size: PolynomialSize,
@@ -198,31 +346,23 @@ impl RLWESecretKey {
         self.0.as_mut_polynomial_list().update_with_wrapping_monic_monomial_mul(degree);
     }
 
-    pub fn to_lwe_secretkey(&mut self, ctx: &mut Context) -> LweSecretKey<BinaryKeyKind, Vec<Scalar>> {
-        let mut sk = RLWESecretKey::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
+    pub fn to_lwe_secretkey(&mut self, ctx: &mut Context) -> LweSecretKey<BinaryKeyKind, Vec<T>> {
+        let mut sk = RLWESecretKey::<T>::generate_binary(ctx.poly_size, &mut ctx.secret_generator);
         sk.clone_from(self);
         return sk.0.into_lwe_secret_key();
     }
 
     
     /// Returns a plaintext with all zeros but a 1 in the index position
-    pub fn plaintext_index(index: usize, ctnt: Scalar, ctx: &mut Context) -> PlaintextList<Vec<Scalar>> {
-        let mut ptxt = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
-        for (i, ptxt) in ptxt.as_mut_polynomial().coefficient_iter_mut().enumerate() {
-            if i == index {
-                ptxt.set_one();
-                *ptxt = ptxt.wrapping_mul(ctnt);
-            };
-        };
-        ptxt
+    pub fn plaintext_index(index: usize, ctnt: T, ctx: &mut Context) -> PlaintextList<Vec<T>> {
+        Self::plaintext_index2(index, ctnt, ctx.plaintext_count())
     }
 
-    pub fn plaintext_index2(index: usize, ctnt: Scalar, count: PlaintextCount) -> PlaintextList<Vec<Scalar>> {
-        let mut ptxt = PlaintextList::allocate(Scalar::zero(), count);
+    pub fn plaintext_index2(index: usize, ctnt: T, count: PlaintextCount) -> PlaintextList<Vec<T>> {
+        let mut ptxt = PlaintextList::allocate(T::ZERO, count);
         for (i, ptxt) in ptxt.as_mut_polynomial().coefficient_iter_mut().enumerate() {
             if i == index {
-                ptxt.set_one();
-                *ptxt = ptxt.wrapping_mul(ctnt);
+                *ptxt = ctnt;
             };
         };
         ptxt
@@ -231,50 +371,24 @@ impl RLWESecretKey {
     /// Generate a trivial secret key where the coefficients are all zero.
     pub fn zero(poly_size: PolynomialSize) -> Self {
         RLWESecretKey(
-            GlweSecretKey::binary_from_container(vec![Scalar::zero(); poly_size.0], poly_size)
+            GlweSecretKey::binary_from_container(vec![T::ZERO; poly_size.0], poly_size)
         )
     }
 
     pub fn fill_with_copy<C>(&mut self, t: &Tensor<C>)
-        where Tensor<C>: AsRefSlice<Element=Scalar> {
+        where Tensor<C>: AsRefSlice<Element=T> {
         self.0.as_mut_tensor().fill_with_copy(t);
     }
 
-    /// Encode and then encrypt the plaintext pt.
-    pub fn binary_encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
-                               ctx: &mut Context)
-    {
-        let mut binary_encoded = pt.clone();
-        poly_binary_encode(&mut binary_encoded.as_mut_polynomial());
-        self.encrypt_rlwe(encrypted, &binary_encoded, ctx.std, &mut ctx.encryption_generator);
-    }
-
-    pub fn binary_encrypt_rlwe_with_noise(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
-        ctx: &mut Context, noise: impl DispersionParameter) {
-        let mut binary_encoded = pt.clone();
-        poly_binary_encode(&mut binary_encoded.as_mut_polynomial());
-        self.encrypt_rlwe(encrypted, &binary_encoded, noise, &mut ctx.encryption_generator);
-    }
-
-
-    /// Encode and then encrypt the plaintext pt.
-    pub fn ternary_encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
-                                ctx: &mut Context)
-    {
-        let mut ternary_encoded = pt.clone();
-        poly_ternary_encode(&mut ternary_encoded.as_mut_polynomial());
-        self.encrypt_rlwe(encrypted, &ternary_encoded, ctx.std, &mut ctx.encryption_generator);
-    }
-
     /// Encrypt a plaintext pt.
     // TODO change API to use Context
-    pub fn encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
+    pub fn encrypt_rlwe(&self, encrypted: &mut RLWECiphertext<T>, pt: &PlaintextList<Vec<T>>,
                         noise_parameter: impl DispersionParameter, generator: &mut EncryptionRandomGenerator) {
         self.0.encrypt_glwe(&mut encrypted.0, pt, noise_parameter, generator);
     }
 
     
-    pub fn fill_rlwe_pk(&self, encrypted: &mut RLWEPublicKey,
+    pub fn fill_rlwe_pk(&self, encrypted: &mut RLWEPublicKey<T>,
         noise_parameter: impl DispersionParameter, generator: &mut EncryptionRandomGenerator, m: usize) {
         for i in 0..m {
             self.0.encrypt_glwe_mask(&mut encrypted.0.get_mut(i).unwrap(), noise_parameter, generator);
@@ -282,23 +396,24 @@ impl RLWESecretKey {
     }
 
     /// Encrypt a scalar.
-    pub fn encrypt_constant_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &Plaintext<Scalar>, ctx: &mut Context) {
-        let mut encoded = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
+    pub fn encrypt_constant_rlwe(&self, encrypted: &mut RLWECiphertext<T>, pt: &Plaintext<T>, ctx: &mut Context) {
+        let mut encoded = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
         *encoded.as_mut_polynomial().get_mut_monomial(MonomialDegree(0)).get_mut_coefficient() = pt.0;
         self.0.encrypt_glwe(&mut encrypted.0, &encoded, ctx.std, &mut ctx.encryption_generator);
     }
 
-    pub fn generate_mask(&self, encrypted: &mut RLWEPublicKey, ctx: &mut Context) {
-        for i in 0..ctx.m {
-            self.0.fill_mask(encrypted.0.get_mut(i).unwrap(), &mut ctx.encryption_generator);
+    /// Fill the masks of the m samples of encrypted from the mask stream of generator.
+    pub fn generate_mask(&self, encrypted: &mut RLWEPublicKey<T>, generator: &mut EncryptionRandomGenerator) {
+        for ct in encrypted.0.iter_mut() {
+            self.0.fill_mask(ct, generator);
         }
     }
 
     /// Decrypt a RLWE ciphertext.
     pub fn decrypt_rlwe(
         &self,
-        pt: &mut PlaintextList<Vec<Scalar>>,
-        encrypted: &RLWECiphertext,
+        pt: &mut PlaintextList<Vec<T>>,
+        encrypted: &RLWECiphertext<T>,
     ) {
         
         self.0.decrypt_glwe(pt, &encrypted.0);
@@ -307,67 +422,65 @@ impl RLWESecretKey {
     /// Decrypt a RLWE ciphertext.
     pub fn decrypt_wrapping_rlwe(
         &self,
-        pt: &mut PlaintextList<Vec<Scalar>>,
-        encrypted: &RLWECiphertext,
+        pt: &mut PlaintextList<Vec<T>>,
+        encrypted: &RLWECiphertext<T>,
     ) {
         
         self.0.decrypt_wrapping_glwe(pt, &encrypted.0);
     }
 
-    /// Decrypt a RLWE ciphertext and then decode.
-    pub fn binary_decrypt_rlwe(
+    /// Compute the share a*s_i + e of the party with id party_id for the threshold decryption of
+    /// ct, e is a polynomial of smudging noise (see `LWESecretKey::partial_decrypt`).
+    pub fn partial_decrypt(
         &self,
-        pt: &mut PlaintextList<Vec<Scalar>>,
-        encrypted: &RLWECiphertext,
-    ) {
-        self.decrypt_rlwe(pt, encrypted);
-        poly_binary_decode(&mut pt.as_mut_polynomial());
-    }
-
-    /// Decrypt a RLWE ciphertext and then decode.
-    pub fn ternary_decrypt_rlwe(
-        &self,
-        pt: &mut PlaintextList<Vec<Scalar>>,
-        encrypted: &RLWECiphertext,
-    ) {
-        self.decrypt_rlwe(pt, encrypted);
-        poly_ternary_decode(&mut pt.as_mut_polynomial());
+        ct: &RLWECiphertext<T>,
+        party_id: usize,
+        smudging_noise: impl DispersionParameter,
+        generator: &mut RandomGenerator,
+    ) -> RLWEDecryptionShare<T> {
+        let mut share = Polynomial::allocate(T::ZERO, self.polynomial_size());
+        generator.fill_tensor_with_random_gaussian(&mut share, 0., smudging_noise.get_standard_dev());
+        share.update_with_wrapping_add_mul(
+            &ct.get_mask().as_polynomial_list().get_polynomial(0),
+            &self.0.as_polynomial_list().get_polynomial(0),
+        );
+        RLWEDecryptionShare { party_id, value: share }
     }
 
     /// Create an RGSW ciphertext of a constant.
-    pub fn encrypt_constant_rgsw(&self, out: &mut RGSWCiphertext,
-                                 pt: &Plaintext<Scalar>,
+    pub fn encrypt_constant_rgsw(&self, out: &mut RGSWCiphertext<T>,
+                                 pt: &Plaintext<T>,
                                  ctx: &mut Context) {
         self.0.encrypt_constant_ggsw(&mut out.0, pt, ctx.std, &mut ctx.encryption_generator)
         // NOTE:for debugging we can use
         //self.0.trivial_encrypt_constant_ggsw(&mut out.0, encoded, ctx.std, &mut ctx.encryption_generator)
     }
 
-    pub fn encrypt_constant_wrapping_rgsw(&self, out: &mut RGSWCiphertext,
-        pt: &Plaintext<Scalar>,
+    pub fn encrypt_constant_wrapping_rgsw(&self, out: &mut RGSWCiphertext<T>,
+        pt: &Plaintext<T>,
         ctx: &mut Context) {
         self.0.encrypt_constant_wrapping_ggsw(&mut out.0, pt, ctx.std_rlwe, &mut ctx.encryption_generator)
     }
 
     /* 
-    pub fn trivial_encrypt_constant_wrapping_rgsw(&self, out: &mut RGSWCiphertext,
-        pt: &Plaintext<Scalar>,
+    pub fn trivial_encrypt_constant_wrapping_rgsw(&self, out: &mut RGSWCiphertext<T>,
+        pt: &Plaintext<T>,
         ctx: &mut Context) {
         self.0.trivial_encrypt_constant_wrapping_ggsw(&mut out.0, pt, ctx.std_rlwe, &mut ctx.encryption_generator)
     }
     */
 
     /// Create an RGSW ciphertext of a polynomial.
-    pub fn encrypt_rgsw(&self, out: &mut RGSWCiphertext, encoded: &PlaintextList<Vec<Scalar>>, ctx: &mut Context) {
+    pub fn encrypt_rgsw(&self, out: &mut RGSWCiphertext<T>, encoded: &PlaintextList<Vec<T>>, ctx: &mut Context) {
         // first create a constant encryption of 0, then add the decomposed encoded value to it
-        self.encrypt_constant_rgsw(out, &Plaintext(Scalar::zero()), ctx);
-        let mut buf = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
+        self.encrypt_constant_rgsw(out, &Plaintext(T::ZERO), ctx);
+        let mut buf = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
         for (i, mut m) in out.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
             let level = (i / 2) + 1;
-            let shift: usize = (Scalar::BITS as usize) - ctx.rgsw_base_log.0 * level;
+            let shift: usize = T::BITS - ctx.rgsw_base_log.0 * level;
             buf.as_mut_tensor().fill_with_copy(encoded.as_tensor());
-            mul_const(&mut buf.as_mut_tensor(), 1 << shift);
-            //println!("Shift when encrypting GSW: {:?}",1 << shift);
+            mul_const(&mut buf.as_mut_tensor(), T::ONE << shift);
+            //println!("Shift when encrypting GSW: {:?}",T::ONE << shift);
             if i % 2 == 0 {
                 // in this case we're in the "top half" of the ciphertext
                 m.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0).update_with_wrapping_add(&buf.as_polynomial());
@@ -378,16 +491,16 @@ impl RLWESecretKey {
         }
     }
 
-    pub fn encrypt_wrapping_rgsw(&self, out: &mut RGSWCiphertext, encoded: &PlaintextList<Vec<Scalar>>, ctx: &mut Context) {
+    pub fn encrypt_wrapping_rgsw(&self, out: &mut RGSWCiphertext<T>, encoded: &PlaintextList<Vec<T>>, ctx: &mut Context) {
         // first create a constant encryption of 0, then add the decomposed encoded value to it
-        self.encrypt_constant_wrapping_rgsw(out, &Plaintext(Scalar::zero()), ctx);
-        let mut buf = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
+        self.encrypt_constant_wrapping_rgsw(out, &Plaintext(T::ZERO), ctx);
+        let mut buf = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
         for (i, mut m) in out.0.as_mut_glwe_list().ciphertext_iter_mut().enumerate() {
             let level = (i / 2) + 1;
-            let shift: usize = (Scalar::BITS as usize) - ctx.rgsw_base_log.0 * level;
+            let shift: usize = T::BITS - ctx.rgsw_base_log.0 * level;
             buf.as_mut_tensor().fill_with_copy(encoded.as_tensor());
-            mul_const(&mut buf.as_mut_tensor(), 1 << shift);
-            //println!("Shift when encrypting GSW: {:?}",1 << shift);
+            mul_const(&mut buf.as_mut_tensor(), T::ONE << shift);
+            //println!("Shift when encrypting GSW: {:?}",T::ONE << shift);
             if i % 2 == 0 {
                 // in this case we're in the "top half" of the ciphertext
                 //println!("MASK = {:?}",m.get_mut_mask().as_mut_polynomial_list().get_mut_polynomial(0));
@@ -400,7 +513,7 @@ impl RLWESecretKey {
     }
 
     /// Create a vector of RGSW ciphertexts of a polynomial.
-    pub fn encrypt_constant_rgsw_vec(&self, v: &Vec<Plaintext<Scalar>>, ctx: &mut Context) -> Vec<RGSWCiphertext> {
+    pub fn encrypt_constant_rgsw_vec(&self, v: &Vec<Plaintext<T>>, ctx: &mut Context) -> Vec<RGSWCiphertext<T>> {
         v.iter().map(|pt| {
             let mut rgsw_ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.rgsw_base_log, ctx.rgsw_level_count);
             self.encrypt_constant_rgsw(&mut rgsw_ct, pt, ctx);
@@ -412,20 +525,20 @@ impl RLWESecretKey {
         self.0.polynomial_size()
     }
 
-    pub fn as_mut_tensor(&mut self) -> &mut Tensor<Vec<Scalar>>{
+    pub fn as_mut_tensor(&mut self) -> &mut Tensor<Vec<T>>{
         self.0.as_mut_tensor()
     }
 
-    pub fn as_tensor(& self) -> &Tensor<Vec<Scalar>>{
+    pub fn as_tensor(& self) -> &Tensor<Vec<T>>{
         self.0.as_tensor()
     }
 
     /// Compute RGSW(-s), where s is self
-    pub fn neg_gsw(&self, ctx: &mut Context) -> RGSWCiphertext {
+    pub fn neg_gsw(&self, ctx: &mut Context) -> RGSWCiphertext<T> {
         let neg_sk = {
-            let mut pt = PlaintextList::allocate(Scalar::zero(), ctx.plaintext_count());
+            let mut pt = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
             for (x, y) in pt.as_mut_tensor().iter_mut().zip(self.0.as_tensor().iter()) {
-                *x = y * Scalar::MAX;
+                *x = y.wrapping_mul(T::MAX);
                 //println!("(x,y) = {:?},{:?}",x,y);
             }
             //println!("pt = {:?}",pt);
@@ -437,6 +550,71 @@ impl RLWESecretKey {
         neg_sk_ct
     }
 
+    /// Compute RGSW(-s), where s is self, with the public key pk instead of self. With the joint
+    /// public key, the sum of the outputs of all the parties is RGSW(-s) for the joint key s.
+    pub fn neg_gsw_with_public_key(&self, pk: &RLWEPublicKey<T>, ctx: &mut Context) -> Result<RGSWCiphertext<T>, MfheError> {
+        let mut neg_sk = PlaintextList::allocate(T::ZERO, ctx.plaintext_count());
+        for (x, y) in neg_sk.as_mut_tensor().iter_mut().zip(self.0.as_tensor().iter()) {
+            *x = y.wrapping_neg();
+        }
+
+        let mut neg_sk_ct = RGSWCiphertext::allocate(ctx.poly_size, ctx.negs_base_log, ctx.negs_level_count);
+        pk.encrypt_rgsw(&mut neg_sk_ct, &neg_sk, ctx)?;
+        Ok(neg_sk_ct)
+    }
+
+}
+
+impl RLWESecretKey {
+    /// Encode and then encrypt the plaintext pt.
+    pub fn binary_encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
+                               ctx: &mut Context) -> Result<(), MfheError>
+    {
+        let mut binary_encoded = pt.clone();
+        poly_binary_encode(&mut binary_encoded.as_mut_polynomial())?;
+        self.encrypt_rlwe(encrypted, &binary_encoded, ctx.std, &mut ctx.encryption_generator);
+        Ok(())
+    }
+
+    pub fn binary_encrypt_rlwe_with_noise(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
+        ctx: &mut Context, noise: impl DispersionParameter) -> Result<(), MfheError> {
+        let mut binary_encoded = pt.clone();
+        poly_binary_encode(&mut binary_encoded.as_mut_polynomial())?;
+        self.encrypt_rlwe(encrypted, &binary_encoded, noise, &mut ctx.encryption_generator);
+        Ok(())
+    }
+
+
+    /// Encode and then encrypt the plaintext pt.
+    pub fn ternary_encrypt_rlwe(&self, encrypted: &mut RLWECiphertext, pt: &PlaintextList<Vec<Scalar>>,
+                                ctx: &mut Context) -> Result<(), MfheError>
+    {
+        let mut ternary_encoded = pt.clone();
+        poly_ternary_encode(&mut ternary_encoded.as_mut_polynomial())?;
+        self.encrypt_rlwe(encrypted, &ternary_encoded, ctx.std, &mut ctx.encryption_generator);
+        Ok(())
+    }
+
+    /// Decrypt a RLWE ciphertext and then decode.
+    pub fn binary_decrypt_rlwe(
+        &self,
+        pt: &mut PlaintextList<Vec<Scalar>>,
+        encrypted: &RLWECiphertext,
+    ) {
+        self.decrypt_rlwe(pt, encrypted);
+        poly_binary_decode(&mut pt.as_mut_polynomial());
+    }
+
+    /// Decrypt a RLWE ciphertext and then decode.
+    pub fn ternary_decrypt_rlwe(
+        &self,
+        pt: &mut PlaintextList<Vec<Scalar>>,
+        encrypted: &RLWECiphertext,
+    ) {
+        self.decrypt_rlwe(pt, encrypted);
+        poly_ternary_decode(&mut pt.as_mut_polynomial());
+    }
+
     pub fn neg_gsw2(list: PlaintextList<Vec<u32>>, ctx: &mut Context) -> PlaintextList<Vec<u64>> {
         let neg_sk = {
             let mut pt = PlaintextList::allocate(0u64, ctx.plaintext_count());
@@ -449,7 +627,6 @@ impl RLWESecretKey {
         };
         neg_sk
     }
-
 }
 
 pub fn pbs_modulus_switch<Scalar>(
@@ -477,7 +654,9 @@ where
 
 
 
-pub fn compute_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encoded_ptxt: &PlaintextList<C>) -> f64
+/// Compute the statistics of the errors of the coefficients of ct, where encoded_ptxt is the
+/// expected encoding (see `NoiseStats`).
+pub fn compute_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encoded_ptxt: &PlaintextList<C>, threshold: Scalar) -> NoiseStats
     where C: AsRefSlice<Element=Scalar>
 {
     // pt = b - a*s = Delta*m + e
@@ -487,24 +666,11 @@ pub fn compute_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encoded_ptxt: &
     // pt = pt - Delta*m = e (encoded_ptxt is Delta*m)
     pt.as_mut_polynomial().update_with_wrapping_sub(&encoded_ptxt.as_polynomial());
 
-    //println!("Error: {:?}", pt);
-
-    let mut max_e = 0f64;
-    for x in pt.as_tensor().iter() {
-        // convert x to signed
-        let z = (*x as SignedScalar).abs() as f64;
-        //println!("z: {}",z);
-        if z > max_e {
-            max_e = z;
-        }
-    }
-    println!("Real noise: {:?}",max_e / ((1u128 << Scalar::BITS) as f64));
-    max_e.log2()
-    //max_e
-    //max_e / ((1u128 << Scalar::BITS) as f64)
+    NoiseStats::from_errors(pt.as_tensor().iter().copied(), threshold)
 }
 
-pub fn compute_wrapping_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encoded_ptxt: &PlaintextList<C>) -> f64
+/// Same as `compute_noise` with the wrapping decryption.
+pub fn compute_wrapping_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encoded_ptxt: &PlaintextList<C>, threshold: Scalar) -> NoiseStats
     where C: AsRefSlice<Element=Scalar>
 {
     // pt = b - a*s = Delta*m + e
@@ -514,71 +680,38 @@ pub fn compute_wrapping_noise<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, encode
     // pt = pt - Delta*m = e (encoded_ptxt is Delta*m)
     pt.as_mut_polynomial().update_with_wrapping_sub(&encoded_ptxt.as_polynomial());
 
-    //println!("Error: {:?}", pt);
-
-    let mut max_e = 0f64;
-    for x in pt.as_tensor().iter() {
-        // convert x to signed
-        let z = (*x as SignedScalar).abs() as f64;
-        //println!("z: {}",z);
-        if z > max_e {
-            max_e = z;
-        }
-    }
-    println!("Real noise: {:?}",max_e / ((1u128 << Scalar::BITS) as f64));
-    max_e.log2()
-    //max_e
-    //max_e / ((1u128 << Scalar::BITS) as f64)
+    NoiseStats::from_errors(pt.as_tensor().iter().copied(), threshold)
 }
 
-pub fn compute_noise_binary<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, ptxt: &PlaintextList<C>) -> f64
+/// Same as `compute_noise` where ptxt is the binary message, see `poly_binary_encode`.
+pub fn compute_noise_binary<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, ptxt: &PlaintextList<C>, threshold: Scalar) -> Result<NoiseStats, MfheError>
     where C: AsRefSlice<Element=Scalar>
 {
-    
     let mut tmp = PlaintextList::allocate(Scalar::zero(), ptxt.count());
     tmp.as_mut_tensor().fill_with_copy(ptxt.as_tensor());
-    poly_binary_encode(&mut tmp.as_mut_polynomial());
-    
-    // pt = b - a*s = Delta*m + e
-    let mut pt = PlaintextList::allocate(Scalar::zero(), tmp.count());
-    sk.decrypt_rlwe(&mut pt, ct);
-
-    // pt = pt - Delta*m = e (encoded_ptxt is Delta*m)
-    pt.as_mut_polynomial().update_with_wrapping_sub(&tmp.as_polynomial());
-
-    //println!("Error: {:?}", pt_poly);
-
-    let mut max_e = 0f64;
-    for x in pt.as_tensor().iter() {
-        // convert x to signed
-        let z = (*x as SignedScalar).abs() as f64;
-        //println!("z: {}",z);
-        if z > max_e {
-            max_e = z;
-        }
-    }
-    println!("Real noise: {:?}",max_e / ((1u128 << Scalar::BITS) as f64));
-    max_e.log2()
+    poly_binary_encode(&mut tmp.as_mut_polynomial())?;
+    Ok(compute_noise(sk, ct, &tmp, threshold))
 }
 
-pub fn compute_noise_ternary<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, ptxt: &PlaintextList<C>) -> f64
+/// Same as `compute_noise` where ptxt is the ternary message, see `poly_ternary_encode`.
+pub fn compute_noise_ternary<C>(sk: &RLWESecretKey, ct: &RLWECiphertext, ptxt: &PlaintextList<C>, threshold: Scalar) -> Result<NoiseStats, MfheError>
     where C: AsRefSlice<Element=Scalar>
 {
     let mut tmp = PlaintextList::allocate(Scalar::zero(), ptxt.count());
     tmp.as_mut_tensor().fill_with_copy(ptxt.as_tensor());
-    poly_ternary_encode(&mut tmp.as_mut_polynomial());
-    compute_noise(sk, ct, &tmp)
+    poly_ternary_encode(&mut tmp.as_mut_polynomial())?;
+    Ok(compute_noise(sk, ct, &tmp, threshold))
 }
 
 
 #[derive(Debug, Clone)]
-/// An RGSW ciphertext.
-/// It is a wrapper around `StandardGgswCiphertext` from concrete.
-pub struct FourierRLWECiphertext(pub(crate) FourierGlweCiphertext<AlignedVec<Complex64>, Scalar>);
+/// An RLWE ciphertext over the torus of T in the Fourier domain.
+/// It is a wrapper around `FourierGlweCiphertext` from concrete.
+pub struct FourierRLWECiphertext<T = Scalar>(pub(crate) FourierGlweCiphertext<AlignedVec<Complex64>, T>);
 
-impl FourierRLWECiphertext {
+impl<T: UnsignedTorus> FourierRLWECiphertext<T> {
 
-    pub fn allocate(poly_size: PolynomialSize) -> FourierRLWECiphertext {
+    pub fn allocate(poly_size: PolynomialSize) -> FourierRLWECiphertext<T> {
         FourierRLWECiphertext(
             FourierGlweCiphertext::allocate(
                 Complex64::new(0., 0.),
@@ -588,7 +721,7 @@ impl FourierRLWECiphertext {
         )
     }
 
-    pub fn fill_with_backward_fourier(&mut self, rlwe: &mut RLWECiphertext, buffers: &mut FourierBuffers<Scalar>) {
+    pub fn fill_with_backward_fourier(&mut self, rlwe: &mut RLWECiphertext<T>, buffers: &mut FourierBuffers<T>) {
         self.0.fill_with_backward_fourier(&mut rlwe.0, buffers);
     }
 
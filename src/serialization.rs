@@ -0,0 +1,415 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::mem::size_of;
+
+use concrete_commons::dispersion::DispersionParameter;
+use bincode::Options;
+use concrete_core::backends::core::private::math::fft::Complex64;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::*;
+use crate::lwe::{CompressedLWEKeyswitchKey, CompressedLWEPublicKey, LWECiphertext, LWECiphertext32, LWEKeyswitchKey, LWEPublicKey, LWESecretKey, LWEtoRLWEKeyswitchKey};
+use crate::circuit_bootstrap::CircuitBootstrappingKey;
+use crate::party::{BootstrappingKeyShare, CircuitBootstrappingKeyShare, CompressedPublicKeyShare};
+use crate::rgsw::{FourierBootstrappingKey, FourierRGSWCiphertext, RGSWCiphertext};
+use crate::server_key::ServerKey;
+
+// Every object is encoded with bincode as a `Header` followed by the object itself. The header
+// records the format version, the type of the object and the parameters of the `Context` it was
+// generated with, so that loading it with other parameters fails instead of giving garbage.
+// The sizes in the encoding come from whoever wrote it, so both reads are bounded: the header by
+// `HEADER_LIMIT` and the object by the `Serializable::size_limit` of the parameters of the header.
+
+/// The version of the encoding, bumped whenever the layout of one of the types changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The maximum size in bytes of an encoded `Header`.
+pub const HEADER_LIMIT: u64 = 4096;
+
+const SCALAR: u64 = size_of::<Scalar>() as u64;
+const COMPLEX: u64 = size_of::<Complex64>() as u64;
+/// A bound on the bytes stored next to the coefficients of one object or ciphertext: the length
+/// prefixes of its vectors, its sizes and its decomposition parameters.
+const OVERHEAD: u64 = 256;
+
+/// The errors returned when an object can not be loaded.
+#[derive(Debug)]
+pub enum SerializationError {
+    /// The encoding itself failed (I/O error or malformed bytes).
+    Bincode(bincode::Error),
+    /// The object was written by another version of the encoding.
+    VersionMismatch { expected: u32, found: u32 },
+    /// The object is not of the requested type.
+    TypeMismatch { expected: &'static str, found: String },
+    /// The object was generated with other parameters than the ones of the context. The parameter
+    /// sets are boxed to keep the error (and every `MfheError`) small.
+    ParameterMismatch { expected: Box<ParameterSet>, found: Box<ParameterSet> },
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Bincode(err) =>
+                write!(f, "bincode error: {}", err),
+            SerializationError::VersionMismatch { expected, found } =>
+                write!(f, "the encoding has version {}, expected {}", found, expected),
+            SerializationError::TypeMismatch { expected, found } =>
+                write!(f, "the encoding holds a {}, expected a {}", found, expected),
+            SerializationError::ParameterMismatch { expected, found } =>
+                write!(f, "the encoding was generated with {:?}, the context uses {:?}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl From<bincode::Error> for SerializationError {
+    fn from(err: bincode::Error) -> Self {
+        SerializationError::Bincode(err)
+    }
+}
+
+/// The parameters of a `Context` that an encoded object depends on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterSet {
+    pub lwe_dimension: usize,
+    pub poly_size: usize,
+    pub k: usize,
+    pub m: usize,
+    pub base_log: usize,
+    pub level_count: usize,
+    pub ks_base_log: usize,
+    pub ks_level_count: usize,
+    pub rgsw_base_log: usize,
+    pub rgsw_level_count: usize,
+    pub negs_base_log: usize,
+    pub negs_level_count: usize,
+    pub std: f64,
+    pub std_lwe: f64,
+    pub std_rlwe: f64,
+}
+
+impl ParameterSet {
+    pub fn from_context(ctx: &Context) -> ParameterSet {
+        ParameterSet {
+            lwe_dimension: ctx.glwe_size.0 - 1,
+            poly_size: ctx.poly_size.0,
+            k: ctx.k,
+            m: ctx.m,
+            base_log: ctx.base_log.0,
+            level_count: ctx.level_count.0,
+            ks_base_log: ctx.ks_base_log.0,
+            ks_level_count: ctx.ks_level_count.0,
+            rgsw_base_log: ctx.rgsw_base_log.0,
+            rgsw_level_count: ctx.rgsw_level_count.0,
+            negs_base_log: ctx.negs_base_log.0,
+            negs_level_count: ctx.negs_level_count.0,
+            std: ctx.std.get_standard_dev(),
+            std_lwe: ctx.std_lwe.get_standard_dev(),
+            std_rlwe: ctx.std_rlwe.get_standard_dev(),
+        }
+    }
+}
+
+// The bounds used by `Serializable::size_limit`, from the sizes of `KeySizeReport`.
+impl ParameterSet {
+    fn max_level_count(&self) -> usize {
+        self.level_count.max(self.ks_level_count).max(self.rgsw_level_count).max(self.negs_level_count)
+    }
+
+    fn lwe_ciphertext(&self, lwe_size: usize) -> u64 {
+        lwe_size as u64 * SCALAR + OVERHEAD
+    }
+
+    fn rlwe_ciphertext(&self) -> u64 {
+        2 * self.poly_size as u64 * SCALAR + OVERHEAD
+    }
+
+    fn rgsw_ciphertext(&self, level_count: usize) -> u64 {
+        (2 * level_count * 2 * self.poly_size) as u64 * SCALAR + OVERHEAD
+    }
+
+    fn fourier_rgsw_ciphertext(&self, level_count: usize) -> u64 {
+        (level_count * 2 * 2 * self.poly_size) as u64 * COMPLEX + OVERHEAD
+    }
+
+    fn keyswitch_key(&self) -> u64 {
+        (self.poly_size * self.ks_level_count) as u64 * self.lwe_ciphertext(self.lwe_dimension + 1) + OVERHEAD
+    }
+}
+
+/// The header written in front of every encoded object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub type_name: String,
+    pub parameters: ParameterSet,
+}
+
+/// The types that can be written with `serialize_into` and read with `deserialize_from`.
+/// TYPE_NAME is stored in the header, it must never change once objects have been written.
+pub trait Serializable: Serialize + DeserializeOwned {
+    const TYPE_NAME: &'static str;
+
+    /// An upper bound on the size in bytes of an object generated with parameters, see
+    /// `deserialize_from`.
+    fn size_limit(parameters: &ParameterSet) -> u64;
+}
+
+impl Serializable for LWECiphertext {
+    const TYPE_NAME: &'static str = "LWECiphertext";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.lwe_ciphertext(parameters.lwe_dimension.max(parameters.poly_size) + 1)
+    }
+}
+
+impl Serializable for LWECiphertext32 {
+    const TYPE_NAME: &'static str = "LWECiphertext32";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        (parameters.poly_size as u64 + 1) * 4 + OVERHEAD
+    }
+}
+
+impl Serializable for LWESecretKey {
+    const TYPE_NAME: &'static str = "LWESecretKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.lwe_ciphertext(parameters.lwe_dimension.max(parameters.poly_size))
+    }
+}
+
+impl Serializable for LWEPublicKey {
+    const TYPE_NAME: &'static str = "LWEPublicKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.m as u64 * parameters.lwe_ciphertext(parameters.lwe_dimension + 1) + OVERHEAD
+    }
+}
+
+impl Serializable for LWEKeyswitchKey {
+    const TYPE_NAME: &'static str = "LWEKeyswitchKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.keyswitch_key()
+    }
+}
+
+impl Serializable for LWEtoRLWEKeyswitchKey {
+    const TYPE_NAME: &'static str = "LWEtoRLWEKeyswitchKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.lwe_dimension as u64 * parameters.rgsw_ciphertext(parameters.ks_level_count) + OVERHEAD
+    }
+}
+
+impl Serializable for RLWECiphertext {
+    const TYPE_NAME: &'static str = "RLWECiphertext";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.rlwe_ciphertext()
+    }
+}
+
+impl Serializable for RLWEPublicKey {
+    const TYPE_NAME: &'static str = "RLWEPublicKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.m as u64 * parameters.rlwe_ciphertext() + OVERHEAD
+    }
+}
+
+impl Serializable for RLWESecretKey {
+    const TYPE_NAME: &'static str = "RLWESecretKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.poly_size as u64 * SCALAR + OVERHEAD
+    }
+}
+
+impl Serializable for RGSWCiphertext {
+    const TYPE_NAME: &'static str = "RGSWCiphertext";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.rgsw_ciphertext(parameters.max_level_count())
+    }
+}
+
+impl Serializable for FourierRGSWCiphertext {
+    const TYPE_NAME: &'static str = "FourierRGSWCiphertext";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.fourier_rgsw_ciphertext(parameters.max_level_count())
+    }
+}
+
+impl Serializable for CompressedLWEPublicKey {
+    const TYPE_NAME: &'static str = "CompressedLWEPublicKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.m as u64 * SCALAR + OVERHEAD
+    }
+}
+
+impl Serializable for CompressedLWEKeyswitchKey {
+    const TYPE_NAME: &'static str = "CompressedLWEKeyswitchKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        (parameters.poly_size * parameters.ks_level_count) as u64 * SCALAR + OVERHEAD
+    }
+}
+
+impl Serializable for CompressedRLWEPublicKey {
+    const TYPE_NAME: &'static str = "CompressedRLWEPublicKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        (parameters.m * parameters.poly_size) as u64 * SCALAR + OVERHEAD
+    }
+}
+
+impl Serializable for CompressedPublicKeyShare {
+    const TYPE_NAME: &'static str = "CompressedPublicKeyShare";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        CompressedLWEPublicKey::size_limit(parameters) + CompressedLWEKeyswitchKey::size_limit(parameters)
+            + CompressedRLWEPublicKey::size_limit(parameters)
+    }
+}
+
+impl Serializable for BootstrappingKeyShare {
+    const TYPE_NAME: &'static str = "BootstrappingKeyShare";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        parameters.lwe_dimension as u64 * parameters.rgsw_ciphertext(parameters.rgsw_level_count) + OVERHEAD
+    }
+}
+
+impl Serializable for CircuitBootstrappingKeyShare {
+    const TYPE_NAME: &'static str = "CircuitBootstrappingKeyShare";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        LWEtoRLWEKeyswitchKey::size_limit(parameters) + parameters.rgsw_ciphertext(parameters.negs_level_count)
+    }
+}
+
+impl Serializable for CircuitBootstrappingKey {
+    const TYPE_NAME: &'static str = "CircuitBootstrappingKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        LWEtoRLWEKeyswitchKey::size_limit(parameters) + parameters.rgsw_ciphertext(parameters.negs_level_count)
+    }
+}
+
+impl Serializable for FourierBootstrappingKey {
+    const TYPE_NAME: &'static str = "FourierBootstrappingKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        (parameters.lwe_dimension * (parameters.k + 1)) as u64 * parameters.fourier_rgsw_ciphertext(parameters.rgsw_level_count) + OVERHEAD
+    }
+}
+
+impl Serializable for ServerKey {
+    const TYPE_NAME: &'static str = "ServerKey";
+
+    fn size_limit(parameters: &ParameterSet) -> u64 {
+        FourierBootstrappingKey::size_limit(parameters) + parameters.keyswitch_key()
+    }
+}
+
+/// Write the header and object to writer.
+pub fn serialize_into<T: Serializable, W: Write>(mut writer: W, object: &T, ctx: &Context) -> Result<(), SerializationError> {
+    let header = Header {
+        version: FORMAT_VERSION,
+        type_name: T::TYPE_NAME.to_string(),
+        parameters: ParameterSet::from_context(ctx),
+    };
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(&mut writer, object)?;
+    Ok(())
+}
+
+/// The options of `bincode::deserialize_from` (the encoding of `bincode::serialize_into`) with a
+/// limit on the bytes read.
+fn limited(limit: u64) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+}
+
+/// Read an object written by `serialize_into`. The header is checked against the version of the
+/// encoding, the requested type and the parameters of ctx before the object is decoded, and the
+/// object can not be larger than `T::size_limit` of these parameters.
+pub fn deserialize_from<T: Serializable, R: Read>(mut reader: R, ctx: &Context) -> Result<T, SerializationError> {
+    let header: Header = limited(HEADER_LIMIT).deserialize_from(&mut reader)?;
+    if header.version != FORMAT_VERSION {
+        return Err(SerializationError::VersionMismatch { expected: FORMAT_VERSION, found: header.version });
+    }
+    if header.type_name != T::TYPE_NAME {
+        return Err(SerializationError::TypeMismatch { expected: T::TYPE_NAME, found: header.type_name });
+    }
+    let expected = ParameterSet::from_context(ctx);
+    if header.parameters != expected {
+        return Err(SerializationError::ParameterMismatch { expected: Box::new(expected), found: Box::new(header.parameters) });
+    }
+    Ok(limited(T::size_limit(&header.parameters)).deserialize_from(&mut reader)?)
+}
+
+/// Encode object in a byte vector, see `serialize_into`.
+pub fn to_bytes<T: Serializable>(object: &T, ctx: &Context) -> Result<Vec<u8>, SerializationError> {
+    let mut bytes = Vec::new();
+    serialize_into(&mut bytes, object, ctx)?;
+    Ok(bytes)
+}
+
+/// Decode an object encoded by `to_bytes`, see `deserialize_from`.
+pub fn from_bytes<T: Serializable>(bytes: &[u8], ctx: &Context) -> Result<T, SerializationError> {
+    deserialize_from(bytes, ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{ContextBuilder, SecurityLevel};
+    use crate::party::{Coordinator, Party};
+
+    fn insecure_context(k: usize) -> Context {
+        ContextBuilder::preset(k, SecurityLevel::Insecure).and_then(|builder| builder.build()).unwrap()
+    }
+
+    #[test]
+    fn server_key_round_trip() {
+        let ctx = insecure_context(2);
+        let mut coordinator: Coordinator = Coordinator::new(&ctx).unwrap();
+        let crs = coordinator.common_reference_string();
+        let mut parties: Vec<Party> = (0..ctx.k).map(|id| Party::new(id, &ctx).unwrap()).collect();
+        let pk_shares: Vec<_> = parties.iter_mut().map(|party| party.public_key_share(&crs)).collect();
+        let joint_public_key = coordinator.aggregate_public_keys(&pk_shares).unwrap().clone();
+        for party in parties.iter_mut() {
+            party.receive_joint_public_key(&joint_public_key).unwrap();
+        }
+        let bsk_shares: Vec<BootstrappingKeyShare> = parties.iter_mut().map(|party| party.bootstrapping_key_share().unwrap()).collect();
+        let share_bytes = to_bytes(&bsk_shares[0], &ctx).unwrap();
+        let share: BootstrappingKeyShare = from_bytes(&share_bytes, &ctx).unwrap();
+        assert_eq!(to_bytes(&share, &ctx).unwrap(), share_bytes);
+
+        let server_key = coordinator.aggregate_bootstrapping_key(&bsk_shares).unwrap();
+        let bytes = to_bytes(&server_key, &ctx).unwrap();
+        assert!(bytes.len() as u64 <= ServerKey::size_limit(&ParameterSet::from_context(&ctx)));
+        let loaded: ServerKey = from_bytes(&bytes, &ctx).unwrap();
+        assert_eq!(loaded.party_count(), ctx.k);
+        assert_eq!(to_bytes(&loaded, &ctx).unwrap(), bytes);
+    }
+
+    #[test]
+    fn oversized_object_is_rejected() {
+        let ctx = insecure_context(1);
+        let pk = LWEPublicKey::allocate(ctx.glwe_size.0, 2 * ctx.m);
+        let bytes = to_bytes(&pk, &ctx).unwrap();
+        match from_bytes::<LWEPublicKey>(&bytes, &ctx) {
+            Err(SerializationError::Bincode(err)) => assert!(matches!(*err, bincode::ErrorKind::SizeLimit)),
+            other => panic!("expected a size limit error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{Mutex, PoisonError};
+
+use concrete_commons::parameters::{GlweSize, LutCountLog, LweSize, PolynomialSize};
+use concrete_core::backends::core::private::crypto::bootstrap::FourierBuffers;
+use concrete_core::backends::core::private::math::torus::UnsignedTorus;
+use get_size::GetSize;
+use crate::*;
+use crate::lwe::{bootstrap_fourier, LWECiphertext, LWEKeyswitchKey};
+use crate::rgsw::{FourierBootstrappingKey, RGSWCiphertext};
+use crate::rlwe::RLWECiphertext;
+use crate::error::MfheError;
+use crate::params::ContextError;
+#[cfg(feature = "serde_serialize")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The evaluation key of the multiparty scheme, over the torus of T (64 bits by default).
+/// It owns the bootstrapping key, i.e. for every LWE coordinate the list of RGSW ciphertexts
+/// output by `homomorphic_indicator`, the LWE keyswitching key and the buffers used for FFT.
+/// The bootstrapping key is converted to the Fourier domain once, when the `ServerKey` is built.
+/// Bootstrapping only borrows the key, so a single `ServerKey` can evaluate any number of gates,
+/// from any number of threads: every call takes a set of FFT buffers from a pool and gives it
+/// back once done, a new set is allocated when all of them are in use.
+/// Only the Fourier bootstrapping key and the keyswitching key are encoded, the pool starts empty
+/// when the key is loaded.
+pub struct ServerKey<T = Scalar> {
+    pub(crate) bootstrapping_key: FourierBootstrappingKey<T>,
+    pub(crate) keyswitch_key: LWEKeyswitchKey<T>,
+    pub(crate) buffers: Mutex<Vec<FourierBuffers<T>>>,
+}
+
+impl<T: UnsignedTorus> GetSize for ServerKey<T> {
+    /// The FFT buffers are not counted.
+    fn get_heap_size(&self) -> usize {
+        GetSize::get_heap_size(&self.bootstrapping_key) + GetSize::get_heap_size(&self.keyswitch_key)
+    }
+}
+
+impl<T: UnsignedTorus> ServerKey<T> {
+    /// The keyswitching key must go from the extracted key of size N back to the LWE key of size n
+    /// covered by the bootstrapping key.
+    pub fn new(bootstrapping_key: Vec<Vec<RGSWCiphertext<T>>>, keyswitch_key: LWEKeyswitchKey<T>) -> Result<ServerKey<T>, MfheError> {
+        let poly_size = match bootstrapping_key.first() {
+            Some(list) => list.first().ok_or(ContextError::NoParties)?.polynomial_size(),
+            None => return Err(ContextError::EmptyLweDimension.into()),
+        };
+        let mut buffers = FourierBuffers::new(poly_size, GlweSize(2));
+        let bootstrapping_key = FourierBootstrappingKey::from_standard(&bootstrapping_key, &mut buffers)?;
+        let server_key = ServerKey::from_fourier(bootstrapping_key, keyswitch_key)?;
+        server_key.buffers.lock().unwrap_or_else(PoisonError::into_inner).push(buffers);
+        Ok(server_key)
+    }
+
+    /// Same as `new` with the bootstrapping key already in the Fourier domain, e.g. a loaded one.
+    pub fn from_fourier(bootstrapping_key: FourierBootstrappingKey<T>, keyswitch_key: LWEKeyswitchKey<T>) -> Result<ServerKey<T>, MfheError> {
+        // The sizes of the keyswitching key are derived from the length of its tensor, they are
+        // only meaningful once its layout is checked.
+        let ksk = &keyswitch_key.0;
+        let (lwe_size, base_log, level_count) = (ksk.lwe_size().0, ksk.decomposition_base_log().0, ksk.decomposition_levels_count().0);
+        if lwe_size == 0 || base_log == 0 || level_count == 0 || base_log * level_count > T::BITS
+            || !ksk.as_tensor().len().is_multiple_of(lwe_size * level_count) {
+            return Err(MfheError::ParameterMismatch("keyswitching key"));
+        }
+
+        let poly_size = bootstrapping_key.polynomial_size();
+        let (input_size, output_size) = (ksk.before_key_size().0, ksk.after_key_size().0);
+        if input_size != poly_size.0 {
+            return Err(MfheError::DimensionMismatch { what: "keyswitching key input", expected: poly_size.0, found: input_size });
+        }
+        if output_size != bootstrapping_key.lwe_dimension() {
+            return Err(MfheError::DimensionMismatch { what: "keyswitching key output", expected: bootstrapping_key.lwe_dimension(), found: output_size });
+        }
+
+        Ok(ServerKey {
+            bootstrapping_key,
+            keyswitch_key,
+            buffers: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn polynomial_size(&self) -> PolynomialSize {
+        self.bootstrapping_key.polynomial_size()
+    }
+
+    /// Return the number of parties k, the indicator lists hold k+1 ciphertexts.
+    pub fn party_count(&self) -> usize {
+        self.bootstrapping_key.party_count()
+    }
+
+    /// Return the size of the LWE ciphertexts that can be bootstrapped (n + 1).
+    pub fn lwe_size(&self) -> LweSize {
+        LweSize(self.bootstrapping_key.lwe_dimension() + 1)
+    }
+
+    pub fn bootstrapping_key(&self) -> &FourierBootstrappingKey<T> {
+        &self.bootstrapping_key
+    }
+
+    pub fn keyswitch_key(&self) -> &LWEKeyswitchKey<T> {
+        &self.keyswitch_key
+    }
+
+    /// Bootstrap lwe_in, rotating the test polynomial stored in accumulator.
+    pub fn bootstrap(&self, lwe_in: &LWECiphertext<T>, accumulator: &mut RLWECiphertext<T>) -> Result<RLWECiphertext<T>, MfheError> {
+        self.multi_value_bootstrap(lwe_in, accumulator, LutCountLog(0))
+    }
+
+    /// Bootstrap lwe_in with an accumulator packing 2^lut_count_log lookup tables.
+    pub fn multi_value_bootstrap(&self, lwe_in: &LWECiphertext<T>, accumulator: &mut RLWECiphertext<T>, lut_count_log: LutCountLog) -> Result<RLWECiphertext<T>, MfheError> {
+        // The lock is only held to take and give back the buffers, not during the bootstrap. The pool
+        // is a plain list of interchangeable buffers, it is consistent even if a thread panicked
+        // while holding the lock, so a poisoned lock is used as is instead of failing every
+        // later bootstrap.
+        let pooled = self.buffers.lock().unwrap_or_else(PoisonError::into_inner).pop();
+        let mut buffers = pooled.unwrap_or_else(|| self.gen_fourier_buffers());
+        let result = self.multi_value_bootstrap_with_buf(lwe_in, accumulator, lut_count_log, &mut buffers);
+        self.buffers.lock().unwrap_or_else(PoisonError::into_inner).push(buffers);
+        result
+    }
+
+    /// Same as `multi_value_bootstrap` with the buffers of the caller instead of the pooled ones,
+    /// e.g. to reuse one set of buffers per rayon thread.
+    pub fn multi_value_bootstrap_with_buf(&self, lwe_in: &LWECiphertext<T>, accumulator: &mut RLWECiphertext<T>, lut_count_log: LutCountLog, buffers: &mut FourierBuffers<T>) -> Result<RLWECiphertext<T>, MfheError> {
+        bootstrap_fourier(lwe_in, accumulator, &self.bootstrapping_key, lut_count_log, buffers)
+    }
+
+    /// Allocate and return buffers that can be used for FFT with this key.
+    pub fn gen_fourier_buffers(&self) -> FourierBuffers<T> {
+        FourierBuffers::new(self.polynomial_size(), GlweSize(2))
+    }
+
+    pub fn keyswitch(&self, after: &mut LWECiphertext<T>, before: &LWECiphertext<T>) {
+        self.keyswitch_key.keyswitch_ciphertext(after, before);
+    }
+}
+
+#[cfg(feature = "serde_serialize")]
+impl<T: UnsignedTorus> Serialize for ServerKey<T>
+    where FourierBootstrappingKey<T>: Serialize, LWEKeyswitchKey<T>: Serialize
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.bootstrapping_key, &self.keyswitch_key).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde_serialize")]
+impl<'de, T: UnsignedTorus> Deserialize<'de> for ServerKey<T>
+    where FourierBootstrappingKey<T>: Deserialize<'de>, LWEKeyswitchKey<T>: Deserialize<'de>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (bootstrapping_key, keyswitch_key) = <(FourierBootstrappingKey<T>, LWEKeyswitchKey<T>)>::deserialize(deserializer)?;
+        ServerKey::from_fourier(bootstrapping_key, keyswitch_key).map_err(de::Error::custom)
+    }
+}